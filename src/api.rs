@@ -4,6 +4,8 @@ use enum_as_inner::EnumAsInner;
 use numwit::Positive;
 
 pub trait OrderBookApi<QuantityT, PriceT, OrderIdT> {
+    /// `condition` is called before any trade occurs.
+    /// If it returns [`ControlFlow::Break`], the buy is aborted, and the book is left exactly as it was before the call.
     fn conditional_buy<BuyAbortReasonT: Debug>(
         &mut self,
         quantity: Positive<QuantityT>,
@@ -11,6 +13,8 @@ pub trait OrderBookApi<QuantityT, PriceT, OrderIdT> {
         condition: impl FnOnce(ConditionalBuyArgs<'_, OrderIdT>) -> ControlFlow<BuyAbortReasonT, ()>,
     ) -> Result<BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>, BuyAbortReasonT>;
 
+    /// `condition` is called before any trade occurs.
+    /// If it returns [`ControlFlow::Break`], the sell is aborted, and the book is left exactly as it was before the call.
     fn conditional_sell<SellAbortReasonT: Debug>(
         &mut self,
         quantity: Positive<QuantityT>,
@@ -50,6 +54,16 @@ pub enum BuyEntryOrExecution<QuantityT, PriceT, OrderIdT> {
         spread: Option<Positive<PriceT>>,
         buyers_remaining: QuantityT,
     },
+    /// The buy traded with every sell it crossed, the last of which was `seller`,
+    /// and `buyers_remaining` entered the book as `id`.
+    PartiallyExecutedThenEntered {
+        id: OrderIdT,
+        seller: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        buyers_remaining: QuantityT,
+    },
+    /// The buy was turned away without trading or entering the book, such as while the book is halted.
+    Rejected,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumAsInner)]
@@ -71,12 +85,369 @@ pub enum SellEntryOrExecution<QuantityT, PriceT, OrderIdT> {
         spread: Option<Positive<PriceT>>,
         buyers_remaining: QuantityT,
     },
+    /// The sell traded with every buy it crossed, the last of which was `buyer`,
+    /// and `sellers_remaining` entered the book as `id`.
+    PartiallyExecutedThenEntered {
+        id: OrderIdT,
+        buyer: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        sellers_remaining: QuantityT,
+    },
+    /// The sell was turned away without trading or entering the book, such as while the book is halted.
+    Rejected,
+}
+
+impl<QuantityT, PriceT, OrderIdT> BuyEntryOrExecution<QuantityT, PriceT, OrderIdT> {
+    pub fn classify(&self) -> ExecutionClass {
+        match self {
+            BuyEntryOrExecution::EnteredOrderBook { .. } => ExecutionClass::Rested,
+            BuyEntryOrExecution::Rejected => ExecutionClass::Rejected,
+            BuyEntryOrExecution::MutualFullExecution { .. }
+            | BuyEntryOrExecution::BuyerFullyExecuted { .. } => ExecutionClass::FullyFilled,
+            BuyEntryOrExecution::SellerFullyExecuted { .. }
+            | BuyEntryOrExecution::PartiallyExecutedThenEntered { .. } => {
+                ExecutionClass::PartiallyFilled
+            }
+        }
+    }
+}
+
+impl<QuantityT, PriceT, OrderIdT> SellEntryOrExecution<QuantityT, PriceT, OrderIdT> {
+    pub fn classify(&self) -> ExecutionClass {
+        match self {
+            SellEntryOrExecution::EnteredOrderBook { .. } => ExecutionClass::Rested,
+            SellEntryOrExecution::Rejected => ExecutionClass::Rejected,
+            SellEntryOrExecution::MutualFullExecution { .. }
+            | SellEntryOrExecution::SellerFullyExecuted { .. } => ExecutionClass::FullyFilled,
+            SellEntryOrExecution::BuyerFullyExecuted { .. }
+            | SellEntryOrExecution::PartiallyExecutedThenEntered { .. } => {
+                ExecutionClass::PartiallyFilled
+            }
+        }
+    }
+}
+
+/// What happened to an incoming order, without the details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExecutionClass {
+    /// The order didn't trade, and entered the book.
+    Rested,
+    /// Some of the order traded, but some is left over, which may have entered the book.
+    PartiallyFilled,
+    /// All of the order traded.
+    FullyFilled,
+    /// None of the order traded, and it didn't enter the book.
+    Rejected,
+}
+
+/// Either a [`BuyEntryOrExecution`] or a [`SellEntryOrExecution`],
+/// for code which handles both sides alike.
+///
+/// `side` is that of the incoming order, and `counterparty` is the resident order it traded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumAsInner)]
+pub enum EntryOrExecution<QuantityT, PriceT, OrderIdT> {
+    EnteredOrderBook {
+        side: Side,
+        id: OrderIdT,
+    },
+    MutualFullExecution {
+        side: Side,
+        counterparty: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+    },
+    BuyerFullyExecuted {
+        side: Side,
+        counterparty: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        sellers_remaining: QuantityT,
+    },
+    SellerFullyExecuted {
+        side: Side,
+        counterparty: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        buyers_remaining: QuantityT,
+    },
+    /// The incoming order traded with every resident order it crossed, the last of which was `counterparty`,
+    /// and `remaining` entered the book as `id`.
+    PartiallyExecutedThenEntered {
+        side: Side,
+        id: OrderIdT,
+        counterparty: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        remaining: QuantityT,
+    },
+    /// The incoming order was turned away without trading or entering the book.
+    Rejected {
+        side: Side,
+    },
+}
+
+impl<QuantityT, PriceT, OrderIdT> EntryOrExecution<QuantityT, PriceT, OrderIdT> {
+    pub fn side(&self) -> Side {
+        match self {
+            EntryOrExecution::EnteredOrderBook { side, .. }
+            | EntryOrExecution::MutualFullExecution { side, .. }
+            | EntryOrExecution::BuyerFullyExecuted { side, .. }
+            | EntryOrExecution::SellerFullyExecuted { side, .. }
+            | EntryOrExecution::PartiallyExecutedThenEntered { side, .. }
+            | EntryOrExecution::Rejected { side } => *side,
+        }
+    }
+
+    pub fn classify(&self) -> ExecutionClass {
+        match (self, self.side()) {
+            (EntryOrExecution::EnteredOrderBook { .. }, _) => ExecutionClass::Rested,
+            (EntryOrExecution::Rejected { .. }, _) => ExecutionClass::Rejected,
+            (EntryOrExecution::MutualFullExecution { .. }, _)
+            | (EntryOrExecution::BuyerFullyExecuted { .. }, Side::Buy)
+            | (EntryOrExecution::SellerFullyExecuted { .. }, Side::Sell) => {
+                ExecutionClass::FullyFilled
+            }
+            (EntryOrExecution::BuyerFullyExecuted { .. }, Side::Sell)
+            | (EntryOrExecution::SellerFullyExecuted { .. }, Side::Buy)
+            | (EntryOrExecution::PartiallyExecutedThenEntered { .. }, _) => {
+                ExecutionClass::PartiallyFilled
+            }
+        }
+    }
+}
+
+impl<QuantityT, PriceT, OrderIdT> From<BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>>
+    for EntryOrExecution<QuantityT, PriceT, OrderIdT>
+{
+    fn from(value: BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>) -> Self {
+        let side = Side::Buy;
+        match value {
+            BuyEntryOrExecution::EnteredOrderBook { id } => {
+                EntryOrExecution::EnteredOrderBook { side, id }
+            }
+            BuyEntryOrExecution::MutualFullExecution { seller, spread } => {
+                EntryOrExecution::MutualFullExecution {
+                    side,
+                    counterparty: seller,
+                    spread,
+                }
+            }
+            BuyEntryOrExecution::BuyerFullyExecuted {
+                seller,
+                spread,
+                sellers_remaining,
+            } => EntryOrExecution::BuyerFullyExecuted {
+                side,
+                counterparty: seller,
+                spread,
+                sellers_remaining,
+            },
+            BuyEntryOrExecution::SellerFullyExecuted {
+                seller,
+                spread,
+                buyers_remaining,
+            } => EntryOrExecution::SellerFullyExecuted {
+                side,
+                counterparty: seller,
+                spread,
+                buyers_remaining,
+            },
+            BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                seller,
+                spread,
+                buyers_remaining,
+            } => EntryOrExecution::PartiallyExecutedThenEntered {
+                side,
+                id,
+                counterparty: seller,
+                spread,
+                remaining: buyers_remaining,
+            },
+            BuyEntryOrExecution::Rejected => EntryOrExecution::Rejected { side },
+        }
+    }
+}
+
+impl<QuantityT, PriceT, OrderIdT> From<SellEntryOrExecution<QuantityT, PriceT, OrderIdT>>
+    for EntryOrExecution<QuantityT, PriceT, OrderIdT>
+{
+    fn from(value: SellEntryOrExecution<QuantityT, PriceT, OrderIdT>) -> Self {
+        let side = Side::Sell;
+        match value {
+            SellEntryOrExecution::EnteredOrderBook { id } => {
+                EntryOrExecution::EnteredOrderBook { side, id }
+            }
+            SellEntryOrExecution::MutualFullExecution { buyer, spread } => {
+                EntryOrExecution::MutualFullExecution {
+                    side,
+                    counterparty: buyer,
+                    spread,
+                }
+            }
+            SellEntryOrExecution::BuyerFullyExecuted {
+                buyer,
+                spread,
+                sellers_remaining,
+            } => EntryOrExecution::BuyerFullyExecuted {
+                side,
+                counterparty: buyer,
+                spread,
+                sellers_remaining,
+            },
+            SellEntryOrExecution::SellerFullyExecuted {
+                buyer,
+                spread,
+                buyers_remaining,
+            } => EntryOrExecution::SellerFullyExecuted {
+                side,
+                counterparty: buyer,
+                spread,
+                buyers_remaining,
+            },
+            SellEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                buyer,
+                spread,
+                sellers_remaining,
+            } => EntryOrExecution::PartiallyExecutedThenEntered {
+                side,
+                id,
+                counterparty: buyer,
+                spread,
+                remaining: sellers_remaining,
+            },
+            SellEntryOrExecution::Rejected => EntryOrExecution::Rejected { side },
+        }
+    }
+}
+
+/// Fails with the original value if it is for a sell.
+impl<QuantityT, PriceT, OrderIdT> TryFrom<EntryOrExecution<QuantityT, PriceT, OrderIdT>>
+    for BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>
+{
+    type Error = EntryOrExecution<QuantityT, PriceT, OrderIdT>;
+
+    fn try_from(value: EntryOrExecution<QuantityT, PriceT, OrderIdT>) -> Result<Self, Self::Error> {
+        if value.side() != Side::Buy {
+            return Err(value);
+        }
+        Ok(match value {
+            EntryOrExecution::EnteredOrderBook { side: _, id } => {
+                BuyEntryOrExecution::EnteredOrderBook { id }
+            }
+            EntryOrExecution::MutualFullExecution {
+                side: _,
+                counterparty,
+                spread,
+            } => BuyEntryOrExecution::MutualFullExecution {
+                seller: counterparty,
+                spread,
+            },
+            EntryOrExecution::BuyerFullyExecuted {
+                side: _,
+                counterparty,
+                spread,
+                sellers_remaining,
+            } => BuyEntryOrExecution::BuyerFullyExecuted {
+                seller: counterparty,
+                spread,
+                sellers_remaining,
+            },
+            EntryOrExecution::SellerFullyExecuted {
+                side: _,
+                counterparty,
+                spread,
+                buyers_remaining,
+            } => BuyEntryOrExecution::SellerFullyExecuted {
+                seller: counterparty,
+                spread,
+                buyers_remaining,
+            },
+            EntryOrExecution::PartiallyExecutedThenEntered {
+                side: _,
+                id,
+                counterparty,
+                spread,
+                remaining,
+            } => BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                seller: counterparty,
+                spread,
+                buyers_remaining: remaining,
+            },
+            EntryOrExecution::Rejected { side: _ } => BuyEntryOrExecution::Rejected,
+        })
+    }
+}
+
+/// Fails with the original value if it is for a buy.
+impl<QuantityT, PriceT, OrderIdT> TryFrom<EntryOrExecution<QuantityT, PriceT, OrderIdT>>
+    for SellEntryOrExecution<QuantityT, PriceT, OrderIdT>
+{
+    type Error = EntryOrExecution<QuantityT, PriceT, OrderIdT>;
+
+    fn try_from(value: EntryOrExecution<QuantityT, PriceT, OrderIdT>) -> Result<Self, Self::Error> {
+        if value.side() != Side::Sell {
+            return Err(value);
+        }
+        Ok(match value {
+            EntryOrExecution::EnteredOrderBook { side: _, id } => {
+                SellEntryOrExecution::EnteredOrderBook { id }
+            }
+            EntryOrExecution::MutualFullExecution {
+                side: _,
+                counterparty,
+                spread,
+            } => SellEntryOrExecution::MutualFullExecution {
+                buyer: counterparty,
+                spread,
+            },
+            EntryOrExecution::BuyerFullyExecuted {
+                side: _,
+                counterparty,
+                spread,
+                sellers_remaining,
+            } => SellEntryOrExecution::BuyerFullyExecuted {
+                buyer: counterparty,
+                spread,
+                sellers_remaining,
+            },
+            EntryOrExecution::SellerFullyExecuted {
+                side: _,
+                counterparty,
+                spread,
+                buyers_remaining,
+            } => SellEntryOrExecution::SellerFullyExecuted {
+                buyer: counterparty,
+                spread,
+                buyers_remaining,
+            },
+            EntryOrExecution::PartiallyExecutedThenEntered {
+                side: _,
+                id,
+                counterparty,
+                spread,
+                remaining,
+            } => SellEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                buyer: counterparty,
+                spread,
+                sellers_remaining: remaining,
+            },
+            EntryOrExecution::Rejected { side: _ } => SellEntryOrExecution::Rejected,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
 #[error("No order found with that ID")]
 pub struct NoSuchOrder;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("An order with that ID is already in the book")]
+pub struct DuplicateId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("Quantity must be greater than zero")]
+pub struct ZeroQuantity;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Cancelled;
 
@@ -92,13 +463,105 @@ pub enum BuyOrSell<QuantityT, PriceT> {
     },
 }
 
+/// A [`BuyOrSell`] which borrows from the order book.
+pub type BuyOrSellRef<'a, QuantityT, PriceT> = BuyOrSell<&'a QuantityT, &'a PriceT>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
 pub trait ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>:
     OrderBookApi<QuantityT, PriceT, OrderIdT>
 {
     /// most-generous first
-    fn buys(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>>;
+    fn iter_buys(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>> + '_;
     /// cheapest first
-    fn sells(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>>;
+    fn iter_sells(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>> + '_;
+    /// most-generous first
+    fn buys(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>> {
+        self.iter_buys().collect()
+    }
+    /// cheapest first
+    fn sells(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>> {
+        self.iter_sells().collect()
+    }
+    fn snapshot(&self) -> BookSnapshot<QuantityT, PriceT, OrderIdT> {
+        BookSnapshot {
+            buys: self.buys(),
+            sells: self.sells(),
+        }
+    }
+    /// Whether both books hold the same orders, in the same priority order, regardless of implementation.
+    fn logically_eq(&self, other: &impl ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>) -> bool
+    where
+        QuantityT: PartialEq,
+        PriceT: PartialEq,
+        OrderIdT: PartialEq,
+    {
+        self.iter_buys().eq(other.iter_buys()) && self.iter_sells().eq(other.iter_sells())
+    }
+    /// Like [`Self::logically_eq`], but ignores ids, so books which assign their own ids may be compared.
+    fn ladder_eq<OtherIdT>(
+        &self,
+        other: &impl ReportingOrderBookApi<QuantityT, PriceT, OtherIdT>,
+    ) -> bool
+    where
+        QuantityT: PartialEq,
+        PriceT: PartialEq,
+    {
+        self.iter_buys()
+            .map(|order| (order.unit_price, order.quantity))
+            .eq(other
+                .iter_buys()
+                .map(|order| (order.unit_price, order.quantity)))
+            && self
+                .iter_sells()
+                .map(|order| (order.unit_price, order.quantity))
+                .eq(other
+                    .iter_sells()
+                    .map(|order| (order.unit_price, order.quantity)))
+    }
+}
+
+/// Every resident order in the book, in priority order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BookSnapshot<QuantityT, PriceT, OrderIdT> {
+    pub buys: Vec<Order<QuantityT, PriceT, OrderIdT>>,
+    pub sells: Vec<Order<QuantityT, PriceT, OrderIdT>>,
+}
+
+/// The total quantity at each of the best prices on each side, best first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DepthSnapshot<QuantityT, PriceT> {
+    pub bids: Vec<(PriceT, QuantityT)>,
+    pub asks: Vec<(PriceT, QuantityT)>,
+}
+
+/// The best levels on each side, with the spread and mid price, as of a single moment.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Quote<QuantityT, PriceT> {
+    pub bids: Vec<(PriceT, QuantityT)>,
+    pub asks: Vec<(PriceT, QuantityT)>,
+    /// [`None`] if either side is empty.
+    pub spread: Option<PriceT>,
+    /// [`None`] if either side is empty.
+    pub mid_price: Option<PriceT>,
+}
+
+/// The best level on each side, as of a single moment.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Touch<QuantityT, PriceT> {
+    pub bid_price: PriceT,
+    pub bid_quantity: QuantityT,
+    /// The number of orders resting at the best bid.
+    pub bid_orders: usize,
+    pub ask_price: PriceT,
+    pub ask_quantity: QuantityT,
+    /// The number of orders resting at the best ask.
+    pub ask_orders: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -108,6 +571,77 @@ pub struct Order<QuantityT, PriceT, OrderIdT> {
     pub id: OrderIdT,
 }
 
+pub trait FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT>:
+    OrderBookApi<QuantityT, PriceT, OrderIdT>
+{
+    /// As [`OrderBookApi::conditional_buy`], additionally reporting every fill in the order they occurred.
+    fn conditional_buy_reporting_fills<BuyAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalBuyArgs<'_, OrderIdT>) -> ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyWithFills<QuantityT, PriceT, OrderIdT>, BuyAbortReasonT>;
+
+    /// As [`OrderBookApi::conditional_sell`], additionally reporting every fill in the order they occurred.
+    fn conditional_sell_reporting_fills<SellAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalSellArgs<'_, OrderIdT>) -> ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellWithFills<QuantityT, PriceT, OrderIdT>, SellAbortReasonT>;
+}
+
+pub type BuyWithFills<QuantityT, PriceT, OrderIdT> = (
+    BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>,
+    Vec<FillReport<QuantityT, PriceT, OrderIdT>>,
+);
+
+pub type SellWithFills<QuantityT, PriceT, OrderIdT> = (
+    SellEntryOrExecution<QuantityT, PriceT, OrderIdT>,
+    Vec<FillReport<QuantityT, PriceT, OrderIdT>>,
+);
+
+/// A single trade between an incoming order (the taker) and a resident order (the maker).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FillReport<QuantityT, PriceT, OrderIdT> {
+    pub maker: OrderIdT,
+    /// The price the trade executed at, which is always the maker's price.
+    pub unit_price: PriceT,
+    /// Filled for both the taker and the maker.
+    pub quantity: QuantityT,
+    pub taker_remaining: QuantityT,
+    pub maker_remaining: QuantityT,
+    /// How much better `unit_price` was than the taker's limit price, if at all.
+    pub price_improvement: Option<Positive<PriceT>>,
+}
+
+/// Order books which can hold orders with ids assigned elsewhere, such as when mirroring another book.
+pub trait RestWithIdOrderBookApi<QuantityT, PriceT, OrderIdT>:
+    OrderBookApi<QuantityT, PriceT, OrderIdT>
+{
+    /// Add an order to the book as `id`, without matching it.
+    fn rest_with_id(
+        &mut self,
+        id: OrderIdT,
+        side: Side,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> Result<(), DuplicateId>;
+}
+
+/// A trade, as it would appear on a time-and-sales tape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trade<QuantityT, PriceT, OrderIdT> {
+    /// The book's sequence number for the submission which caused this trade.
+    pub sequence: u64,
+    /// The side of the incoming order.
+    pub taker_side: Side,
+    pub maker: OrderIdT,
+    pub unit_price: PriceT,
+    pub quantity: QuantityT,
+}
+
 pub trait UnconditionalOrderBookApi<QuantityT, PriceT, OrderIdT>:
     OrderBookApi<QuantityT, PriceT, OrderIdT>
 {
@@ -121,6 +655,30 @@ pub trait UnconditionalOrderBookApi<QuantityT, PriceT, OrderIdT>:
         quantity: Positive<QuantityT>,
         unit_price: PriceT,
     ) -> SellEntryOrExecution<QuantityT, PriceT, OrderIdT>;
+    /// Like [`Self::unconditional_buy`], for a `quantity` which hasn't been checked.
+    fn try_buy(
+        &mut self,
+        quantity: QuantityT,
+        unit_price: PriceT,
+    ) -> Result<BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>, ZeroQuantity>
+    where
+        QuantityT: num::Zero + PartialOrd,
+    {
+        let quantity = Positive::new(quantity).map_err(|_| ZeroQuantity)?;
+        Ok(self.unconditional_buy(quantity, unit_price))
+    }
+    /// Like [`Self::unconditional_sell`], for a `quantity` which hasn't been checked.
+    fn try_sell(
+        &mut self,
+        quantity: QuantityT,
+        unit_price: PriceT,
+    ) -> Result<SellEntryOrExecution<QuantityT, PriceT, OrderIdT>, ZeroQuantity>
+    where
+        QuantityT: num::Zero + PartialOrd,
+    {
+        let quantity = Positive::new(quantity).map_err(|_| ZeroQuantity)?;
+        Ok(self.unconditional_sell(quantity, unit_price))
+    }
 }
 
 impl<T, QuantityT, PriceT, OrderIdT> UnconditionalOrderBookApi<QuantityT, PriceT, OrderIdT> for T
@@ -153,3 +711,214 @@ where
         }
     }
 }
+
+/// An object-safe counterpart to [`ReportingOrderBookApi`], so that different
+/// implementations can be used through a `Box<dyn DynOrderBook<...>>`.
+pub trait DynOrderBook<QuantityT, PriceT, OrderIdT> {
+    fn unconditional_buy(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>;
+    fn unconditional_sell(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> SellEntryOrExecution<QuantityT, PriceT, OrderIdT>;
+    fn query(&self, id: OrderIdT) -> Result<BuyOrSell<QuantityT, PriceT>, NoSuchOrder>;
+    fn cancel(&mut self, id: OrderIdT) -> Result<Cancelled, NoSuchOrder>;
+    fn buys(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>>;
+    fn sells(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>>;
+}
+
+impl<T, QuantityT, PriceT, OrderIdT> DynOrderBook<QuantityT, PriceT, OrderIdT> for T
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>,
+{
+    fn unconditional_buy(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> BuyEntryOrExecution<QuantityT, PriceT, OrderIdT> {
+        UnconditionalOrderBookApi::unconditional_buy(self, quantity, unit_price)
+    }
+
+    fn unconditional_sell(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> SellEntryOrExecution<QuantityT, PriceT, OrderIdT> {
+        UnconditionalOrderBookApi::unconditional_sell(self, quantity, unit_price)
+    }
+
+    fn query(&self, id: OrderIdT) -> Result<BuyOrSell<QuantityT, PriceT>, NoSuchOrder> {
+        OrderBookApi::query(self, id)
+    }
+
+    fn cancel(&mut self, id: OrderIdT) -> Result<Cancelled, NoSuchOrder> {
+        OrderBookApi::cancel(self, id)
+    }
+
+    fn buys(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>> {
+        ReportingOrderBookApi::buys(self)
+    }
+
+    fn sells(&self) -> Vec<Order<QuantityT, PriceT, OrderIdT>> {
+        ReportingOrderBookApi::sells(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BuyEntryOrExecution, EntryOrExecution, ExecutionClass, SellEntryOrExecution, Side,
+    };
+    use numwit::Positive;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn entry_or_execution_round_trips() {
+        let spread = Positive::new(1).ok();
+        let buys = [
+            BuyEntryOrExecution::EnteredOrderBook { id: 'a' },
+            BuyEntryOrExecution::MutualFullExecution {
+                seller: 'b',
+                spread,
+            },
+            BuyEntryOrExecution::BuyerFullyExecuted {
+                seller: 'c',
+                spread,
+                sellers_remaining: 2,
+            },
+            BuyEntryOrExecution::SellerFullyExecuted {
+                seller: 'd',
+                spread,
+                buyers_remaining: 3,
+            },
+            BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                id: 'e',
+                seller: 'f',
+                spread,
+                buyers_remaining: 4,
+            },
+            BuyEntryOrExecution::Rejected,
+        ];
+        for buy in buys {
+            let unified = EntryOrExecution::from(buy);
+            assert_eq!(Side::Buy, unified.side());
+            assert_eq!(Err(unified), SellEntryOrExecution::try_from(unified));
+            assert_eq!(Ok(buy), BuyEntryOrExecution::try_from(unified));
+        }
+        let sells = [
+            SellEntryOrExecution::EnteredOrderBook { id: 'a' },
+            SellEntryOrExecution::MutualFullExecution { buyer: 'b', spread },
+            SellEntryOrExecution::BuyerFullyExecuted {
+                buyer: 'c',
+                spread,
+                sellers_remaining: 2,
+            },
+            SellEntryOrExecution::SellerFullyExecuted {
+                buyer: 'd',
+                spread,
+                buyers_remaining: 3,
+            },
+            SellEntryOrExecution::PartiallyExecutedThenEntered {
+                id: 'e',
+                buyer: 'f',
+                spread,
+                sellers_remaining: 4,
+            },
+            SellEntryOrExecution::Rejected,
+        ];
+        for sell in sells {
+            let unified = EntryOrExecution::from(sell);
+            assert_eq!(Side::Sell, unified.side());
+            assert_eq!(Err(unified), BuyEntryOrExecution::try_from(unified));
+            assert_eq!(Ok(sell), SellEntryOrExecution::try_from(unified));
+        }
+    }
+
+    #[test]
+    fn classify_is_from_the_incoming_order() {
+        use ExecutionClass::{FullyFilled, PartiallyFilled, Rejected, Rested};
+
+        let spread = Positive::new(1).ok();
+        let buys = [
+            (Rested, BuyEntryOrExecution::EnteredOrderBook { id: 'a' }),
+            (
+                FullyFilled,
+                BuyEntryOrExecution::MutualFullExecution {
+                    seller: 'b',
+                    spread,
+                },
+            ),
+            (
+                FullyFilled,
+                BuyEntryOrExecution::BuyerFullyExecuted {
+                    seller: 'c',
+                    spread,
+                    sellers_remaining: 2,
+                },
+            ),
+            (
+                PartiallyFilled,
+                BuyEntryOrExecution::SellerFullyExecuted {
+                    seller: 'd',
+                    spread,
+                    buyers_remaining: 3,
+                },
+            ),
+            (
+                PartiallyFilled,
+                BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                    id: 'e',
+                    seller: 'f',
+                    spread,
+                    buyers_remaining: 4,
+                },
+            ),
+            (Rejected, BuyEntryOrExecution::Rejected),
+        ];
+        for (class, buy) in buys {
+            assert_eq!(class, buy.classify());
+            assert_eq!(class, EntryOrExecution::from(buy).classify());
+        }
+        let sells = [
+            (Rested, SellEntryOrExecution::EnteredOrderBook { id: 'a' }),
+            (
+                FullyFilled,
+                SellEntryOrExecution::MutualFullExecution { buyer: 'b', spread },
+            ),
+            (
+                PartiallyFilled,
+                SellEntryOrExecution::BuyerFullyExecuted {
+                    buyer: 'c',
+                    spread,
+                    sellers_remaining: 2,
+                },
+            ),
+            (
+                FullyFilled,
+                SellEntryOrExecution::SellerFullyExecuted {
+                    buyer: 'd',
+                    spread,
+                    buyers_remaining: 3,
+                },
+            ),
+            (
+                PartiallyFilled,
+                SellEntryOrExecution::PartiallyExecutedThenEntered {
+                    id: 'e',
+                    buyer: 'f',
+                    spread,
+                    sellers_remaining: 4,
+                },
+            ),
+            (Rejected, SellEntryOrExecution::Rejected),
+        ];
+        for (class, sell) in sells {
+            assert_eq!(class, sell.classify());
+            assert_eq!(class, EntryOrExecution::from(sell).classify());
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Execution metrics, emitted through the [`metrics`](https://docs.rs/metrics) crate
+//! when the `metrics` feature is enabled, and otherwise compiled away.
+//!
+//! | name                            | kind      | labels |
+//! | ------------------------------- | --------- | ------ |
+//! | `orderbook_orders_submitted`    | counter   | `side` |
+//! | `orderbook_order_size`          | histogram | `side` |
+//! | `orderbook_trades_executed`     | counter   | `side` |
+//! | `orderbook_volume_traded`       | counter   | `side` |
+//! | `orderbook_price_improvement`   | histogram | `side` |
+//! | `orderbook_cancels`             | counter   | `side` |
+//!
+//! `side` is that of the incoming order, or the cancelled order.
+
+use crate::api::{FillReport, Side};
+
+/// Quantities and prices which can be recorded as metrics.
+///
+/// This is implemented for the primitive numbers, and books over other types need an implementation,
+/// whether or not the `metrics` feature is enabled.
+/// Those which don't override the methods are counted, but their quantities and prices aren't recorded.
+pub trait Measurable {
+    /// This as a whole number, to add to counters such as `orderbook_volume_traded`.
+    fn as_u64(&self) -> Option<u64> {
+        None
+    }
+    /// This as a float, to record in histograms such as `orderbook_order_size`.
+    fn as_f64(&self) -> Option<f64> {
+        None
+    }
+}
+
+macro_rules! measurable {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Measurable for $ty {
+                fn as_u64(&self) -> Option<u64> {
+                    num::ToPrimitive::to_u64(self)
+                }
+                fn as_f64(&self) -> Option<f64> {
+                    num::ToPrimitive::to_f64(self)
+                }
+            }
+        )*
+    };
+}
+
+measurable!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+#[cfg(feature = "metrics")]
+fn side_label(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
+
+pub(crate) fn order_submitted<QuantityT: Measurable>(side: Side, quantity: &QuantityT) {
+    #[cfg(feature = "metrics")]
+    {
+        let side = side_label(side);
+        metrics::counter!("orderbook_orders_submitted", "side" => side).increment(1);
+        metrics::histogram!("orderbook_order_size", "side" => side)
+            .record(quantity.as_f64().unwrap_or_default());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (side, quantity);
+}
+
+pub(crate) fn trades_executed<QuantityT, PriceT, OrderIdT>(
+    side: Side,
+    fills: &[FillReport<QuantityT, PriceT, OrderIdT>],
+) where
+    QuantityT: Measurable,
+    PriceT: Measurable + Clone,
+{
+    #[cfg(feature = "metrics")]
+    {
+        let side = side_label(side);
+        for fill in fills {
+            metrics::counter!("orderbook_trades_executed", "side" => side).increment(1);
+            metrics::counter!("orderbook_volume_traded", "side" => side)
+                .increment(fill.quantity.as_u64().unwrap_or_default());
+            metrics::histogram!("orderbook_price_improvement", "side" => side).record(
+                fill.price_improvement
+                    .clone()
+                    .and_then(|it| it.into_inner().as_f64())
+                    .unwrap_or_default(),
+            );
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (side, fills);
+}
+
+pub(crate) fn order_cancelled(side: Side) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("orderbook_cancels", "side" => side_label(side)).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = side;
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::{
+        api::{OrderBookApi as _, UnconditionalOrderBookApi as _},
+        price_level_b_tree_order_book::PriceLevelBTreeOrderBook,
+    };
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use numwit::Positive;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn counters_advance() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let mut order_book = PriceLevelBTreeOrderBook::<usize, usize, _>::default();
+            order_book.unconditional_sell(Positive::new(2).unwrap(), 10);
+            order_book.unconditional_sell(Positive::new(3).unwrap(), 10);
+            let id = *order_book
+                .unconditional_sell(Positive::new(1).unwrap(), 11)
+                .as_entered_order_book()
+                .unwrap();
+            order_book.unconditional_buy(Positive::new(4).unwrap(), 10);
+            order_book.cancel(id).unwrap();
+        });
+        let counters = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, _, _, value)| match value {
+                DebugValue::Counter(count) => Some((
+                    (
+                        key.key().name().to_owned(),
+                        key.key().labels().next().unwrap().value().to_owned(),
+                    ),
+                    count,
+                )),
+                _ => None,
+            })
+            .collect::<BTreeMap<_, _>>();
+        let counter = |name: &str, side: &str, count| ((name.to_owned(), side.to_owned()), count);
+        assert_eq!(
+            BTreeMap::from([
+                counter("orderbook_cancels", "sell", 1),
+                counter("orderbook_orders_submitted", "buy", 1),
+                counter("orderbook_orders_submitted", "sell", 3),
+                counter("orderbook_trades_executed", "buy", 2),
+                counter("orderbook_volume_traded", "buy", 4),
+            ]),
+            counters
+        );
+    }
+}
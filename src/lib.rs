@@ -1,4 +1,12 @@
 pub mod api;
+pub mod execution_metrics;
 pub mod price_level_b_tree_order_book;
+#[cfg(test)]
+mod property_suite;
+#[cfg(test)]
+mod reference_order_book;
+pub mod scaled_price;
+pub mod snapping_order_book;
 pub mod test_suite;
 mod util;
+pub mod wire;
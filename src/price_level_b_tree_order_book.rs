@@ -1,23 +1,79 @@
 use crate::api::{
-    BuyEntryOrExecution, BuyOrSell, Cancelled, ConditionalBuyArgs, ConditionalSellArgs,
-    NoSuchOrder, Order, OrderBookApi, ReportingOrderBookApi, SellEntryOrExecution,
+    BookSnapshot, BuyEntryOrExecution, BuyOrSell, BuyOrSellRef, BuyWithFills, Cancelled,
+    ConditionalBuyArgs, ConditionalSellArgs, DepthSnapshot, DuplicateId, FillReport,
+    FillReportingOrderBookApi, NoSuchOrder, Order, OrderBookApi, Quote, ReportingOrderBookApi,
+    RestWithIdOrderBookApi, SellEntryOrExecution, SellWithFills, Side, Touch, Trade,
 };
-use crate::util::{BTreeMapExt as _, NonEmpty};
+use crate::execution_metrics::{self, Measurable};
+use crate::util::{BTreeMapExt as _, Fnv1a, NonEmpty};
+use crate::wire::{DecodeError, Wire};
 use num::Unsigned;
 use numwit::Positive;
 use std::{
     cmp::Ordering,
-    collections::{BTreeMap, HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    convert::Infallible,
     fmt::Debug,
-    ops::{self, ControlFlow},
+    hash::{Hash, Hasher as _},
+    num::NonZeroUsize,
+    ops::{self, ControlFlow, RangeInclusive},
 };
 use tap::Tap as _;
 
+/// The resting orders on one side of the book, by price, each level in time priority.
+type Levels<QuantityT, PriceT, OrderIdT> =
+    BTreeMap<PriceT, NonEmpty<VecDeque<(OrderIdT, QuantityT)>>>;
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "QuantityT: serde::Deserialize<'de>, \
+                               PriceT: serde::Deserialize<'de> + Ord, \
+                               OrderIdT: serde::Deserialize<'de> + Eq + Hash"))
+)]
 pub struct PriceLevelBTreeOrderBook<QuantityT, PriceT, OrderIdT> {
     buys: BTreeMap<PriceT, NonEmpty<VecDeque<(OrderIdT, QuantityT)>>>,
     sells: BTreeMap<PriceT, NonEmpty<VecDeque<(OrderIdT, QuantityT)>>>,
     ids_to_price_level: HashMap<OrderIdT, BuyOrSellAtPriceLevel<PriceT>>,
+    /// The most recently filled or cancelled orders, oldest first.
+    completed: VecDeque<(OrderIdT, OrderStatus)>,
+    completed_capacity: usize,
+    /// The makers each order traded with on entry, kept while it rests or is in `completed`.
+    counterparties: HashMap<OrderIdT, Vec<(OrderIdT, QuantityT)>>,
+    stale_index_policy: StaleIndexPolicy,
+    /// The most recent trades, oldest first.
+    recent_trades: VecDeque<Trade<QuantityT, PriceT, OrderIdT>>,
+    recent_trades_capacity: usize,
+    /// Bumped by every call which changes the book.
+    sequence: u64,
+    /// The least each resting order which set one may trade at once.
+    min_quantities: HashMap<OrderIdT, QuantityT>,
+    /// Quantity set aside from resting orders, invisible to matching.
+    holds: HashMap<HoldToken, Hold<QuantityT, PriceT, OrderIdT>>,
+    next_hold: u64,
+    /// Total price improvement given to takers since the session was reset, if any.
+    session_spread_captured: Option<PriceT>,
+    /// How many times a resting order has been matched at each price, which outlives the level.
+    level_hits: BTreeMap<PriceT, u64>,
+    /// Placeholder best prices, which stand in for orders which haven't arrived yet.
+    bid_hint: Option<PriceT>,
+    ask_hint: Option<PriceT>,
+    /// Incoming orders skip resting orders they are priced further than this through.
+    max_adverse_spread: Option<PriceT>,
+    /// Incoming orders don't trade further than this from the last trade.
+    price_collar: Option<PriceCollar<PriceT>>,
+    last_trade_price: Option<PriceT>,
+    status: BookStatus,
+    /// The most recently removed orders, oldest first.
+    removals: VecDeque<OrderRemoved<QuantityT, PriceT, OrderIdT>>,
+    removals_capacity: usize,
+    /// Sums of `price * quantity`, if [kept](Self::with_notional_totals).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    notional: Option<NotionalTracker<QuantityT, PriceT>>,
+    #[cfg(feature = "serde")]
+    #[serde(skip)]
+    trade_log: Option<TradeLog<QuantityT, PriceT, OrderIdT>>,
 }
 
 impl<QuantityT, PriceT, OrderIdT> Default
@@ -28,339 +84,6438 @@ impl<QuantityT, PriceT, OrderIdT> Default
             buys: Default::default(),
             sells: Default::default(),
             ids_to_price_level: Default::default(),
+            completed: Default::default(),
+            completed_capacity: 0,
+            counterparties: Default::default(),
+            stale_index_policy: StaleIndexPolicy::Panic,
+            recent_trades: Default::default(),
+            recent_trades_capacity: 0,
+            sequence: 0,
+            min_quantities: Default::default(),
+            holds: Default::default(),
+            next_hold: 0,
+            session_spread_captured: None,
+            level_hits: Default::default(),
+            bid_hint: None,
+            ask_hint: None,
+            max_adverse_spread: None,
+            price_collar: None,
+            last_trade_price: None,
+            status: BookStatus::Open,
+            removals: Default::default(),
+            removals_capacity: 0,
+            notional: None,
+            #[cfg(feature = "serde")]
+            trade_log: None,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum BuyOrSellAtPriceLevel<T> {
-    Buy(T),
-    Sell(T),
+/// Writes each trade to the writer passed to [`PriceLevelBTreeOrderBook::with_trade_log`].
+///
+/// Clones of the book share the writer.
+#[cfg(feature = "serde")]
+#[derive(Clone)]
+struct TradeLog<QuantityT, PriceT, OrderIdT> {
+    #[allow(clippy::type_complexity)]
+    write: std::sync::Arc<
+        std::sync::Mutex<
+            dyn FnMut(&Trade<QuantityT, PriceT, OrderIdT>) -> std::io::Result<()> + Send,
+        >,
+    >,
+    error_policy: TradeLogErrorPolicy,
+    /// The write which stopped the log, under [`TradeLogErrorPolicy::Stop`].
+    error: Option<std::sync::Arc<std::io::Error>>,
 }
 
-impl<QuantityT, PriceT> OrderBookApi<QuantityT, PriceT, uuid::Uuid>
-    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+#[cfg(feature = "serde")]
+impl<QuantityT, PriceT, OrderIdT> Debug for TradeLog<QuantityT, PriceT, OrderIdT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TradeLog")
+            .field("error_policy", &self.error_policy)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sums of `price * quantity` over resting orders, on each side and at each level,
+/// kept by [`PriceLevelBTreeOrderBook::with_notional_totals`].
+#[derive(Debug, Clone, PartialEq)]
+struct NotionalTotals<PriceT, NotionalT> {
+    buys: NotionalT,
+    sells: NotionalT,
+    /// Levels with no notional are left out.
+    buy_levels: BTreeMap<PriceT, NotionalT>,
+    sell_levels: BTreeMap<PriceT, NotionalT>,
+}
+
+impl<PriceT, NotionalT> NotionalTotals<PriceT, NotionalT>
 where
-    QuantityT: Unsigned + Clone + Ord + Debug,
-    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+    PriceT: Clone + Ord,
+    NotionalT: Clone + num::Zero,
 {
-    #[tracing::instrument(skip(self, condition), ret)]
-    fn conditional_buy<BuyAbortReasonT: Debug>(
-        &mut self,
-        quantity: Positive<QuantityT>,
-        unit_price: PriceT,
-        condition: impl FnOnce(
-            ConditionalBuyArgs<'_, uuid::Uuid>,
-        ) -> std::ops::ControlFlow<BuyAbortReasonT, ()>,
-    ) -> Result<BuyEntryOrExecution<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
-        let quantity = quantity.into_inner();
-        let entry_or_exc = match self.sells.first_entry() {
-            // A trade could occur
-            Some(ask_level)
-                if {
-                    let ask_price = ask_level.key();
-                    ask_price <= &unit_price
-                } =>
-            {
-                if let ControlFlow::Break(reason) = condition(ConditionalBuyArgs {
-                    seller_id: &ask_level.get().front().0,
-                }) {
-                    return Err(reason);
-                }
-                // a trade will occur
-                let (ask_price, level) = ask_level.remove_entry();
-                let (remaining_level, (seller_id, seller_quantity)) = level.pop_front();
-
-                let spread = match ask_price.cmp(&unit_price) {
-                    Ordering::Less => Some(Positive::new(unit_price - ask_price.clone()).unwrap()),
-                    Ordering::Equal => None,
-                    Ordering::Greater => unreachable!("already checked"),
-                };
-
-                match quantity.cmp(&seller_quantity) {
-                    // buyer (us) wants less than the seller has
-                    Ordering::Less => {
-                        let sellers_remaining = seller_quantity - quantity;
-                        self.sells.insert_uncontended(
-                            ask_price,
-                            match remaining_level {
-                                Some(remaining) => remaining.tap_mut(|it| {
-                                    it.push_front((seller_id, sellers_remaining.clone()))
-                                }),
-                                None => NonEmpty::vecdeque((seller_id, sellers_remaining.clone())),
-                            },
-                        );
-                        BuyEntryOrExecution::BuyerFullyExecuted {
-                            seller: seller_id,
-                            spread,
-                            sellers_remaining,
-                        }
-                    }
-                    Ordering::Equal => {
-                        self.ids_to_price_level.remove(&seller_id);
-                        if let Some(remaining_level) = remaining_level {
-                            self.sells.insert_uncontended(ask_price, remaining_level)
-                        }
-                        BuyEntryOrExecution::MutualFullExecution {
-                            seller: seller_id,
-                            spread,
-                        }
-                    }
-                    // buyer (us) wants more than the seller has
-                    Ordering::Greater => {
-                        let buyers_remaining = seller_quantity - quantity;
-                        self.ids_to_price_level.remove(&seller_id);
-                        if let Some(remaining_level) = remaining_level {
-                            self.sells.insert_uncontended(ask_price, remaining_level)
-                        }
-                        BuyEntryOrExecution::SellerFullyExecuted {
-                            seller: seller_id,
-                            spread,
-                            buyers_remaining,
-                        }
-                    }
-                }
-            }
-            // Ask is too high, or no sellers
-            Some(_) | None => {
-                let id = uuid::Uuid::new_v4();
-                self.buys
-                    .entry(unit_price.clone())
-                    .and_modify(|level| level.push_back((id, quantity.clone())))
-                    .or_insert_with(|| NonEmpty::vecdeque((id, quantity)));
-                self.ids_to_price_level
-                    .entry(id)
-                    .and_modify(|_| panic!("uuid collision"))
-                    .or_insert(BuyOrSellAtPriceLevel::Buy(unit_price));
-                BuyEntryOrExecution::EnteredOrderBook { id }
-            }
-        };
-        Ok(entry_or_exc)
+    fn new() -> Self {
+        Self {
+            buys: NotionalT::zero(),
+            sells: NotionalT::zero(),
+            buy_levels: BTreeMap::new(),
+            sell_levels: BTreeMap::new(),
+        }
     }
 
-    #[tracing::instrument(skip(self, condition), ret)]
-    fn conditional_sell<SellAbortReasonT: Debug>(
-        &mut self,
-        quantity: Positive<QuantityT>,
-        unit_price: PriceT,
-        condition: impl FnOnce(
-            ConditionalSellArgs<'_, uuid::Uuid>,
-        ) -> std::ops::ControlFlow<SellAbortReasonT, ()>,
-    ) -> Result<SellEntryOrExecution<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
-        let quantity = quantity.into_inner();
-        let entry_or_exc = match self.buys.last_entry() {
-            // A trade could occur
-            Some(bid_level)
-                if {
-                    let bid_price = bid_level.key();
-                    bid_price >= &unit_price
-                } =>
-            {
-                if let ControlFlow::Break(reason) = condition(ConditionalSellArgs {
-                    buyer_id: &bid_level.get().front().0,
-                }) {
-                    return Err(reason);
-                }
-                // a trade will occur
-                let (bid_price, level) = bid_level.remove_entry();
-                let (remaining_level, (buyer_id, buyer_quantity)) = level.pop_front();
-
-                let spread = match bid_price.cmp(&unit_price) {
-                    Ordering::Less => unreachable!("already checked"),
-                    Ordering::Equal => None,
-                    Ordering::Greater => {
-                        Some(Positive::new(bid_price.clone() - unit_price).unwrap())
-                    }
-                };
+    fn total(&self, side: Side) -> NotionalT {
+        match side {
+            Side::Buy => self.buys.clone(),
+            Side::Sell => self.sells.clone(),
+        }
+    }
 
-                match quantity.cmp(&buyer_quantity) {
-                    // seller (us) wants less than the buyer has
-                    Ordering::Less => {
-                        let buyers_remaining = buyer_quantity - quantity;
-                        self.sells.insert_uncontended(
-                            bid_price,
-                            match remaining_level {
-                                Some(remaining_level) => remaining_level.tap_mut(|it| {
-                                    it.push_front((buyer_id, buyers_remaining.clone()))
-                                }),
-                                None => NonEmpty::vecdeque((buyer_id, buyers_remaining.clone())),
-                            },
-                        );
-                        SellEntryOrExecution::SellerFullyExecuted {
-                            buyer: buyer_id,
-                            spread,
-                            buyers_remaining,
-                        }
-                    }
-                    Ordering::Equal => {
-                        self.ids_to_price_level.remove(&buyer_id);
-                        if let Some(remaining_level) = remaining_level {
-                            self.buys.insert_uncontended(bid_price, remaining_level)
-                        }
-                        SellEntryOrExecution::MutualFullExecution {
-                            buyer: buyer_id,
-                            spread,
-                        }
-                    }
-                    // seller (us) wants more than the buyer has
-                    Ordering::Greater => {
-                        let sellers_remaining = buyer_quantity - quantity;
-                        self.ids_to_price_level.remove(&buyer_id);
-                        if let Some(remaining_level) = remaining_level {
-                            self.sells.insert_uncontended(bid_price, remaining_level)
-                        }
-                        SellEntryOrExecution::BuyerFullyExecuted {
-                            buyer: buyer_id,
-                            spread,
-                            sellers_remaining,
-                        }
-                    }
-                }
-            }
-            // No bids are high enough, or no buyers
-            Some(_) | None => {
-                let id = uuid::Uuid::new_v4();
-                self.sells
-                    .entry(unit_price.clone())
-                    .and_modify(|level| level.push_back((id, quantity.clone())))
-                    .or_insert_with(|| NonEmpty::vecdeque((id, quantity)));
-                self.ids_to_price_level
-                    .entry(id)
-                    .and_modify(|_| panic!("uuid collision"))
-                    .or_insert(BuyOrSellAtPriceLevel::Sell(unit_price));
-                SellEntryOrExecution::EnteredOrderBook { id }
-            }
+    fn at_price(&self, side: Side, price: &PriceT) -> NotionalT {
+        let levels = match side {
+            Side::Buy => &self.buy_levels,
+            Side::Sell => &self.sell_levels,
         };
-        Ok(entry_or_exc)
+        levels.get(price).cloned().unwrap_or_else(NotionalT::zero)
     }
 
-    #[tracing::instrument(skip(self), ret)]
-    fn query(&self, id: uuid::Uuid) -> Result<BuyOrSell<QuantityT, PriceT>, NoSuchOrder> {
-        match self.ids_to_price_level.get(&id) {
-            Some(BuyOrSellAtPriceLevel::Buy(level)) => {
-                let quantity = self
-                    .buys
-                    .get(level)
-                    .expect("stale ids_to_price_level")
-                    .iter()
-                    .find_map(|(it_id, quantity)| match it_id == &id {
-                        true => Some(quantity.clone()),
-                        false => None,
-                    })
-                    .expect("stale ids_to_price_level");
-                Ok(BuyOrSell::Buy {
-                    quantity,
-                    unit_price: level.clone(),
-                })
-            }
-            Some(BuyOrSellAtPriceLevel::Sell(level)) => {
-                let quantity = self
-                    .sells
-                    .get(level)
-                    .expect("stale ids_to_price_level")
-                    .iter()
-                    .find_map(|(it_id, quantity)| match it_id == &id {
-                        true => Some(quantity.clone()),
-                        false => None,
-                    })
-                    .expect("stale ids_to_price_level");
-                Ok(BuyOrSell::Sell {
-                    quantity,
-                    unit_price: level.clone(),
-                })
-            }
-            None => Err(NoSuchOrder),
+    /// Apply `change` to the totals of `side` and of its level at `price`.
+    fn adjust(&mut self, side: Side, price: &PriceT, change: impl Fn(NotionalT) -> NotionalT) {
+        let (total, levels) = match side {
+            Side::Buy => (&mut self.buys, &mut self.buy_levels),
+            Side::Sell => (&mut self.sells, &mut self.sell_levels),
+        };
+        *total = change(total.clone());
+        let level = change(levels.remove(price).unwrap_or_else(NotionalT::zero));
+        if !level.is_zero() {
+            levels.insert(price.clone(), level);
         }
     }
+}
 
-    #[tracing::instrument(skip(self), ret)]
-    fn cancel(&mut self, id: uuid::Uuid) -> Result<Cancelled, NoSuchOrder> {
-        match self.ids_to_price_level.remove(&id) {
-            Some(BuyOrSellAtPriceLevel::Buy(price)) => {
-                let level = self.buys.remove(&price).expect("stale ids_to_price_level");
-                match level.pop_once_by(|(it_id, _)| it_id == &id) {
-                    (Some(remaining_level), (_, _quantity)) => {
-                        self.buys.insert_uncontended(price, remaining_level)
-                    }
-                    (None, (_, _quantity)) => {}
-                }
-                Ok(Cancelled)
-            }
-            Some(BuyOrSellAtPriceLevel::Sell(price)) => {
-                let level = self.sells.remove(&price).expect("stale ids_to_price_level");
-                match level.pop_once_by(|(it_id, _)| it_id == &id) {
-                    (Some(remaining_level), (_, _quantity)) => {
-                        self.buys.insert_uncontended(price, remaining_level)
-                    }
-                    (None, (_, _quantity)) => {}
-                }
-                Ok(Cancelled)
-            }
-            None => Err(NoSuchOrder),
-        }
-    }
+/// [`NotionalTotals`] in whichever `NotionalT` they were kept in, which the book doesn't name.
+trait TrackNotional<QuantityT, PriceT>: Debug + Send + Sync {
+    fn entered(&mut self, side: Side, price: &PriceT, quantity: &QuantityT);
+    fn left(&mut self, side: Side, price: &PriceT, quantity: &QuantityT);
+    /// Start again from `orders`, after the levels have changed wholesale.
+    fn recount(&mut self, orders: &mut dyn Iterator<Item = (Side, &PriceT, &QuantityT)>);
+    /// Whether these are the totals of `orders`.
+    fn counts(&self, orders: &mut dyn Iterator<Item = (Side, &PriceT, &QuantityT)>) -> bool;
+    fn clone_box(&self) -> Box<dyn TrackNotional<QuantityT, PriceT>>;
+    fn as_any(&self) -> &dyn std::any::Any;
 }
 
-impl<QuantityT, PriceT> ReportingOrderBookApi<QuantityT, PriceT, uuid::Uuid>
-    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+impl<QuantityT, PriceT, NotionalT> TrackNotional<QuantityT, PriceT>
+    for NotionalTotals<PriceT, NotionalT>
 where
-    QuantityT: Unsigned + Clone + Ord + Debug,
-    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+    QuantityT: Clone + Into<NotionalT>,
+    PriceT: Clone + Ord + Into<NotionalT> + Debug + Send + Sync + 'static,
+    NotionalT: Clone
+        + PartialEq
+        + Debug
+        + Send
+        + Sync
+        + 'static
+        + num::Zero
+        + ops::Mul<Output = NotionalT>
+        + ops::Sub<Output = NotionalT>,
 {
-    fn buys(&self) -> Vec<Order<QuantityT, PriceT, uuid::Uuid>> {
-        self.buys
-            .iter()
-            .rev()
-            .flat_map(|(price, level)| {
-                level.iter().map(|(id, quantity)| Order {
-                    quantity: quantity.clone(),
-                    unit_price: price.clone(),
-                    id: *id,
-                })
-            })
-            .collect()
+    fn entered(&mut self, side: Side, price: &PriceT, quantity: &QuantityT) {
+        let notional = price.clone().into() * quantity.clone().into();
+        self.adjust(side, price, |total| total + notional.clone())
     }
 
-    fn sells(&self) -> Vec<Order<QuantityT, PriceT, uuid::Uuid>> {
-        self.sells
-            .iter()
-            .flat_map(|(price, level)| {
-                level.iter().map(|(id, quantity)| Order {
-                    quantity: quantity.clone(),
-                    unit_price: price.clone(),
-                    id: *id,
-                })
-            })
-            .collect()
+    fn left(&mut self, side: Side, price: &PriceT, quantity: &QuantityT) {
+        let notional = price.clone().into() * quantity.clone().into();
+        self.adjust(side, price, |total| total - notional.clone())
+    }
+
+    fn recount(&mut self, orders: &mut dyn Iterator<Item = (Side, &PriceT, &QuantityT)>) {
+        *self = Self::new();
+        for (side, price, quantity) in orders {
+            TrackNotional::<QuantityT, PriceT>::entered(self, side, price, quantity)
+        }
+    }
+
+    fn counts(&self, orders: &mut dyn Iterator<Item = (Side, &PriceT, &QuantityT)>) -> bool {
+        let mut recounted = Self::new();
+        TrackNotional::<QuantityT, PriceT>::recount(&mut recounted, orders);
+        recounted == *self
+    }
+
+    fn clone_box(&self) -> Box<dyn TrackNotional<QuantityT, PriceT>> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::PriceLevelBTreeOrderBook;
-
-    macro_rules! do_test_suite {
-        ($ty:ty {
-            $($fn_name:ident),* $(,)?
-        }) => {
-            $(
-                #[test_log::test]
-                fn $fn_name() {
-                    crate::test_suite::$fn_name::<$ty, _, _, _>();
-                }
-            )*
+/// A boxed [`TrackNotional`], which the book can clone.
+struct NotionalTracker<QuantityT, PriceT>(Box<dyn TrackNotional<QuantityT, PriceT>>);
+
+impl<QuantityT, PriceT> Clone for NotionalTracker<QuantityT, PriceT> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+
+impl<QuantityT, PriceT> Debug for NotionalTracker<QuantityT, PriceT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Each resting order's side, price and quantity, buys then sells.
+fn resting<'a, QuantityT, PriceT, OrderIdT>(
+    buys: &'a Levels<QuantityT, PriceT, OrderIdT>,
+    sells: &'a Levels<QuantityT, PriceT, OrderIdT>,
+) -> impl Iterator<Item = (Side, &'a PriceT, &'a QuantityT)> {
+    let side = |side, levels: &'a Levels<QuantityT, PriceT, OrderIdT>| {
+        levels.iter().flat_map(move |(price, level)| {
+            level
+                .iter()
+                .map(move |(_, quantity)| (side, price, quantity))
+        })
+    };
+    side(Side::Buy, buys).chain(side(Side::Sell, sells))
+}
+
+/// What to do if writing to the trade log fails.
+///
+/// Either way, the book itself is unaffected, and the failure is logged at the error level.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TradeLogErrorPolicy {
+    #[default]
+    Panic,
+    /// Stop writing, keeping the error for [`PriceLevelBTreeOrderBook::trade_log_error`].
+    Stop,
+}
+
+/// Identifies quantity set aside by [`PriceLevelBTreeOrderBook::hold`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HoldToken(u64);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Hold<QuantityT, PriceT, OrderIdT> {
+    id: OrderIdT,
+    side: Side,
+    unit_price: PriceT,
+    quantity: QuantityT,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum HoldError {
+    #[error("No order found with that ID")]
+    NoSuchOrder,
+    #[error("an order must keep some quantity free to hold the rest")]
+    InsufficientQuantity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("No hold found with that token")]
+pub struct NoSuchHold;
+
+/// A price would have been moved out of the range of its type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("price out of range")]
+pub struct PriceOutOfRange;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum ReleaseError {
+    #[error("No hold found with that token")]
+    NoSuchHold,
+    #[error("the held order has left the book, so the hold can only be committed")]
+    NoSuchOrder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("Less than the minimum quantity would execute")]
+pub struct MinimumQuantityNotMet;
+
+/// Returned by [`PriceLevelBTreeOrderBook::cancel_if`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CancelOutcome {
+    Cancelled,
+    /// The predicate returned `false`, so the order was left in the book.
+    Kept,
+}
+
+/// Returned by [`PriceLevelBTreeOrderBook::amend`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AmendOutcome<QuantityT, PriceT, OrderIdT> {
+    /// Only the quantity went down, so the order kept its place in its queue.
+    KeptPriority,
+    /// The order moved to the back of the queue at its new price.
+    LostPriority,
+    /// The order crossed the book at its new price, so traded as if incoming.
+    /// Any remainder rests under the same id, at the back of the queue.
+    Executed {
+        fills: Vec<FillReport<QuantityT, PriceT, OrderIdT>>,
+    },
+    /// The order crossed levels it couldn't trade with at its new price, protected by
+    /// [`PriceLevelBTreeOrderBook::with_max_adverse_spread`] or beyond [`PriceLevelBTreeOrderBook::with_price_collar`],
+    /// so whatever was left after trading with the others was cancelled rather than rest crossed.
+    Cancelled {
+        fills: Vec<FillReport<QuantityT, PriceT, OrderIdT>>,
+    },
+    /// The book is [halted](PriceLevelBTreeOrderBook::halt) and the order would have crossed it at its new price,
+    /// so the order was left as it was.
+    Rejected,
+}
+
+/// Returned by [`PriceLevelBTreeOrderBook::cancel_reporting_level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CancelReport {
+    /// No orders remain at the cancelled order's price, so the level has been removed.
+    pub level_now_empty: bool,
+}
+
+/// Returned by [`PriceLevelBTreeOrderBook::buy_reporting_rest`] and [`PriceLevelBTreeOrderBook::sell_reporting_rest`]
+/// when the order enters the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RestReport<PriceT> {
+    /// The best price on the other side when the order arrived,
+    /// or [`None`] if there were no orders there at all.
+    pub best_opposing: Option<PriceT>,
+}
+
+/// Returned by [`PriceLevelBTreeOrderBook::market_buy`] and [`PriceLevelBTreeOrderBook::market_sell`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MarketExecution<QuantityT, PriceT, OrderIdT> {
+    /// Every fill, in the order they occurred.
+    pub fills: Vec<FillReport<QuantityT, PriceT, OrderIdT>>,
+    pub filled: QuantityT,
+    /// The mean price of `fills`, weighted by quantity, or [`None`] if nothing traded.
+    ///
+    /// Rounded as [`ops::Div`] rounds, so truncated for integer prices.
+    pub average_price: Option<PriceT>,
+    /// What was left once the other side ran out, which doesn't rest.
+    pub unfilled: QuantityT,
+}
+
+/// What to do if `ids_to_price_level` is found to be out of sync with the price levels,
+/// which indicates a bug.
+///
+/// Either way, the offending order is logged at the error level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StaleIndexPolicy {
+    #[default]
+    Panic,
+    /// Treat the order as if it doesn't exist, reporting [`NoSuchOrder`].
+    Recover,
+}
+
+impl<QuantityT, PriceT, OrderIdT> PriceLevelBTreeOrderBook<QuantityT, PriceT, OrderIdT> {
+    /// The orders resting on `side`.
+    fn levels(&self, side: Side) -> &Levels<QuantityT, PriceT, OrderIdT> {
+        match side {
+            Side::Buy => &self.buys,
+            Side::Sell => &self.sells,
+        }
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut Levels<QuantityT, PriceT, OrderIdT> {
+        match side {
+            Side::Buy => &mut self.buys,
+            Side::Sell => &mut self.sells,
+        }
+    }
+
+    /// The orders an incoming order on `taker` trades with.
+    fn makers(&self, taker: Side) -> &Levels<QuantityT, PriceT, OrderIdT> {
+        match taker {
+            Side::Buy => &self.sells,
+            Side::Sell => &self.buys,
+        }
+    }
+
+    /// Matching takes levels out of the map this returns, and puts what's left of them back into the same map.
+    fn makers_mut(&mut self, taker: Side) -> &mut Levels<QuantityT, PriceT, OrderIdT> {
+        match taker {
+            Side::Buy => &mut self.sells,
+            Side::Sell => &mut self.buys,
+        }
+    }
+
+    /// An empty book with room for `n_orders` resting orders in its index before reallocating.
+    pub fn with_capacity(n_orders: usize) -> Self {
+        Self {
+            ids_to_price_level: HashMap::with_capacity(n_orders),
+            ..Default::default()
+        }
+    }
+
+    /// Remember the last `capacity` filled or cancelled orders,
+    /// so that [`Self::query_status`] and [`Self::fills_of`] can report them.
+    pub fn with_completed_capacity(self, capacity: usize) -> Self {
+        Self {
+            completed: VecDeque::with_capacity(capacity),
+            completed_capacity: capacity,
+            ..self
+        }
+    }
+
+    pub fn with_stale_index_policy(self, stale_index_policy: StaleIndexPolicy) -> Self {
+        Self {
+            stale_index_policy,
+            ..self
+        }
+    }
+
+    /// Placeholder best prices for warming up from a sparse feed, before the orders at them have arrived.
+    ///
+    /// Placeholders are never orders: they only affect [`Self::highest_bid_price`] and [`Self::lowest_ask_price`].
+    /// Each is dropped once a real order rests at or through its price.
+    pub fn with_top_of_book_hint(self, bid: Option<PriceT>, ask: Option<PriceT>) -> Self {
+        Self {
+            bid_hint: bid,
+            ask_hint: ask,
+            ..self
+        }
+    }
+
+    /// Write each trade to `writer` as it happens, as a line of JSON.
+    #[cfg(feature = "serde")]
+    pub fn with_trade_log(
+        self,
+        mut writer: impl std::io::Write + Send + 'static,
+        error_policy: TradeLogErrorPolicy,
+    ) -> Self
+    where
+        QuantityT: serde::Serialize + 'static,
+        PriceT: serde::Serialize + 'static,
+        OrderIdT: serde::Serialize + 'static,
+    {
+        let write = move |trade: &Trade<QuantityT, PriceT, OrderIdT>| {
+            serde_json::to_writer(&mut writer, trade)?;
+            writer.write_all(b"\n")
         };
+        Self {
+            trade_log: Some(TradeLog {
+                write: std::sync::Arc::new(std::sync::Mutex::new(write)),
+                error_policy,
+                error: None,
+            }),
+            ..self
+        }
     }
 
-    do_test_suite! {PriceLevelBTreeOrderBook<usize, usize, uuid::Uuid> {
-        default_is_empty,
-        add_query_remove_single_buy_order,
-        add_query_remove_single_sell_order,
-        single_resident_buy_is_fully_executed,
-        single_resident_sell_is_fully_executed,
-        buys_reported_with_price_time_priority,
-        sells_reported_with_price_time_priority,
-        buys_execute_with_price_time_priority,
-        sells_execute_with_price_time_priority,
-    }}
+    /// The failure which stopped the trade log, under [`TradeLogErrorPolicy::Stop`].
+    #[cfg(feature = "serde")]
+    pub fn trade_log_error(&self) -> Option<&std::io::Error> {
+        self.trade_log.as_ref()?.error.as_deref()
+    }
+
+    /// Keep sums of `price * quantity` in `NotionalT` as the book changes, on each side and at each level,
+    /// so that [`Self::total_buy_notional`], [`Self::total_sell_notional`] and [`Self::notional_at_price`]
+    /// read them rather than walking the book when asked for the same `NotionalT`.
+    ///
+    /// `NotionalT` may be wider than `PriceT` and `QuantityT`, so that the sums don't overflow.
+    /// The sums aren't serialized.
+    pub fn with_notional_totals<NotionalT>(self) -> Self
+    where
+        QuantityT: Clone + Into<NotionalT>,
+        PriceT: Clone + Ord + Into<NotionalT> + Debug + Send + Sync + 'static,
+        NotionalT: Clone
+            + PartialEq
+            + Debug
+            + Send
+            + Sync
+            + 'static
+            + num::Zero
+            + ops::Mul<Output = NotionalT>
+            + ops::Sub<Output = NotionalT>,
+    {
+        let mut totals = NotionalTotals::<PriceT, NotionalT>::new();
+        TrackNotional::<QuantityT, PriceT>::recount(
+            &mut totals,
+            &mut resting(&self.buys, &self.sells),
+        );
+        Self {
+            notional: Some(NotionalTracker(Box::new(totals))),
+            ..self
+        }
+    }
+
+    /// The [notional totals](Self::with_notional_totals), if they are kept in `NotionalT`.
+    fn notional_totals<NotionalT>(&self) -> Option<&NotionalTotals<PriceT, NotionalT>>
+    where
+        PriceT: 'static,
+        NotionalT: 'static,
+    {
+        self.notional.as_ref()?.0.as_any().downcast_ref()
+    }
+
+    /// Keep any [notional totals](Self::with_notional_totals) up to date with `quantity` coming to rest at `price`.
+    fn notional_entered(&mut self, side: Side, price: &PriceT, quantity: &QuantityT) {
+        if let Some(notional) = &mut self.notional {
+            notional.0.entered(side, price, quantity)
+        }
+    }
+
+    /// Keep any [notional totals](Self::with_notional_totals) up to date with `quantity` leaving `price`.
+    fn notional_left(&mut self, side: Side, price: &PriceT, quantity: &QuantityT) {
+        if let Some(notional) = &mut self.notional {
+            notional.0.left(side, price, quantity)
+        }
+    }
+
+    /// Protect resting orders from incoming orders priced more than `max_adverse_spread` through them,
+    /// as in a fast market.
+    ///
+    /// Such an incoming order skips the protected levels, and trades only with the levels it crosses by no more
+    /// than `max_adverse_spread`.
+    /// Whatever is left would cross the protected levels, so it doesn't rest:
+    /// an order which traded reports the last maker as fully executed,
+    /// and one which didn't is [rejected](crate::api::BuyEntryOrExecution::Rejected).
+    pub fn with_max_adverse_spread(self, max_adverse_spread: PriceT) -> Self {
+        Self {
+            max_adverse_spread: Some(max_adverse_spread),
+            ..self
+        }
+    }
+
+    /// Stop incoming orders trading at prices further than `collar.band` from the last trade,
+    /// to protect them from sweeping a thin book.
+    ///
+    /// An order priced beyond the band trades as if priced at its edge.
+    /// If that leaves some of it which would have traded beyond the band, `collar.remainder` decides whether
+    /// that rests at the edge of the band or is dropped, as if [rejected](crate::api::BuyEntryOrExecution::Rejected)
+    /// when none of the order traded.
+    /// Market and immediate-or-cancel orders never rest, so whatever is left is returned unfilled.
+    ///
+    /// Nothing is collared until the book has traded.
+    pub fn with_price_collar(self, collar: PriceCollar<PriceT>) -> Self {
+        Self {
+            price_collar: Some(collar),
+            ..self
+        }
+    }
+
+    /// The price of the most recent trade, which [`Self::with_price_collar`] measures from.
+    pub fn last_trade_price(&self) -> Option<&PriceT> {
+        self.last_trade_price.as_ref()
+    }
+
+    /// Remember the last `capacity` removals, for [`Self::recent_removals`].
+    pub fn with_removals_capacity(self, capacity: usize) -> Self {
+        Self {
+            removals: VecDeque::with_capacity(capacity),
+            removals_capacity: capacity,
+            ..self
+        }
+    }
+
+    /// Remember the last `capacity` trades, for [`Self::recent_trades`].
+    pub fn with_recent_trades_capacity(self, capacity: usize) -> Self {
+        Self {
+            recent_trades: VecDeque::with_capacity(capacity),
+            recent_trades_capacity: capacity,
+            ..self
+        }
+    }
+
+    /// Called when `ids_to_price_level` has `id` at `price_level`, but it isn't there.
+    fn stale_index(&self, id: &OrderIdT, price_level: &BuyOrSellAtPriceLevel<PriceT>) -> NoSuchOrder
+    where
+        OrderIdT: Debug,
+        PriceT: Debug,
+    {
+        tracing::error!(?id, ?price_level, "stale ids_to_price_level");
+        match self.stale_index_policy {
+            StaleIndexPolicy::Panic => panic!("stale ids_to_price_level for order {id:?}"),
+            StaleIndexPolicy::Recover => NoSuchOrder,
+        }
+    }
+
+    /// The number of calls which have changed the book, for ordering events across books.
+    ///
+    /// Submitting an order which isn't aborted, cancelling an order, and resting an order
+    /// with [`RestWithIdOrderBookApi::rest_with_id`] each advance this by one.
+    pub fn current_sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    pub fn status(&self) -> BookStatus {
+        self.status
+    }
+
+    /// Stop trading, rejecting incoming orders until the book is [resumed](PriceLevelBTreeOrderBook::resume).
+    ///
+    /// A rejected order is reported as [`BuyEntryOrExecution::Rejected`] or [`SellEntryOrExecution::Rejected`],
+    /// and is never given an id.
+    pub fn halt(&mut self) {
+        self.status = BookStatus::Halted
+    }
+
+    /// Stop trading, resting incoming orders without matching until the book is
+    /// [resumed](PriceLevelBTreeOrderBook::resume).
+    pub fn halt_for_auction(&mut self) {
+        self.status = BookStatus::AuctionOnly
+    }
+
+    fn record_completed(&mut self, id: OrderIdT, status: OrderStatus)
+    where
+        OrderIdT: Eq + Hash,
+    {
+        if self.completed_capacity == 0 {
+            self.counterparties.remove(&id);
+            return;
+        }
+        if self.completed.len() == self.completed_capacity {
+            if let Some((forgotten, _)) = self.completed.pop_front() {
+                self.counterparties.remove(&forgotten);
+            }
+        }
+        self.completed.push_back((id, status))
+    }
+}
+
+/// See [`PriceLevelBTreeOrderBook::query_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OrderStatus {
+    Resting,
+    Filled,
+    Cancelled,
+    /// The order never entered the book, or has been forgotten since it was completed.
+    Unknown,
+}
+
+/// Why an order left the book. See [`PriceLevelBTreeOrderBook::recent_removals`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RemovalReason {
+    Filled,
+    /// Part of the order traded, and the rest stays in the book.
+    PartiallyFilled,
+    Cancelled,
+    /// Dropped by [`PriceLevelBTreeOrderBook::trim_to_levels`].
+    Evicted,
+    /// Held quantity taken by [`PriceLevelBTreeOrderBook::commit`].
+    /// Any free quantity stays in the book.
+    Committed,
+}
+
+/// Quantity leaving the book, however it left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderRemoved<QuantityT, PriceT, OrderIdT> {
+    pub id: OrderIdT,
+    pub reason: RemovalReason,
+    pub side: Side,
+    pub price: PriceT,
+    /// The quantity which left, whether it traded or not,
+    /// including any held quantity dropped with an order which was cancelled or evicted.
+    pub quantity: QuantityT,
+}
+
+/// See [`PriceLevelBTreeOrderBook::with_price_collar`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PriceCollar<PriceT> {
+    /// How far from the last trade an incoming order may trade.
+    pub band: PriceT,
+    pub remainder: CollarRemainder,
+}
+
+/// What happens to the part of an incoming order which would have traded beyond a [`PriceCollar`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CollarRemainder {
+    Cancel,
+    /// Rest at the edge of the band.
+    Rest,
+}
+
+/// Which way [`PriceLevelBTreeOrderBook::shift_prices`] moves prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShiftDirection {
+    Up,
+    Down,
+}
+
+/// Whether incoming orders may trade. See [`PriceLevelBTreeOrderBook::halt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BookStatus {
+    #[default]
+    Open,
+    /// Incoming orders are rejected, leaving the book untouched.
+    Halted,
+    /// Incoming orders rest without matching, until the book is [resumed](PriceLevelBTreeOrderBook::resume).
+    AuctionOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum BuyOrSellAtPriceLevel<T> {
+    Buy(T),
+    Sell(T),
+}
+
+impl<QuantityT, PriceT, OrderIdT> PriceLevelBTreeOrderBook<QuantityT, PriceT, OrderIdT>
+where
+    QuantityT: Clone,
+    PriceT: Ord,
+    OrderIdT: Clone,
+{
+    /// The number of resting orders on both sides, read from the index rather than by walking the levels.
+    pub fn order_count(&self) -> usize {
+        self.ids_to_price_level.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids_to_price_level.is_empty()
+    }
+
+    /// Fold `f` over every resting order without collecting them,
+    /// buys most-generous first, then sells cheapest first.
+    pub fn fold_orders<AccumulatorT>(
+        &self,
+        init: AccumulatorT,
+        mut f: impl FnMut(AccumulatorT, Side, &Order<QuantityT, PriceT, OrderIdT>) -> AccumulatorT,
+    ) -> AccumulatorT
+    where
+        QuantityT: Clone,
+        PriceT: Clone,
+    {
+        let buys = self.buys.iter().rev().map(|level| (Side::Buy, level));
+        let sells = self.sells.iter().map(|level| (Side::Sell, level));
+        buys.chain(sells)
+            .fold(init, |accumulator, (side, (unit_price, level))| {
+                level
+                    .iter()
+                    .fold(accumulator, |accumulator, (id, quantity)| {
+                        let order = Order {
+                            quantity: quantity.clone(),
+                            unit_price: unit_price.clone(),
+                            id: id.clone(),
+                        };
+                        f(accumulator, side, &order)
+                    })
+            })
+    }
+
+    /// The orders resident at `price`, in the order they will be executed.
+    /// Returns [`None`] if there are no orders at that price.
+    pub fn level_queue(&self, side: Side, price: &PriceT) -> Option<Vec<(OrderIdT, QuantityT)>> {
+        let level = self.levels(side).get(price)?;
+        Some(level.iter().cloned().collect())
+    }
+
+    /// Consume the book, moving out its buys, most-generous first, like [`ReportingOrderBookApi::iter_buys`].
+    ///
+    /// Only each order's price is cloned. Held quantity isn't included.
+    pub fn into_buys(self) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>>
+    where
+        PriceT: Clone,
+    {
+        self.buys.into_iter().rev().flat_map(into_orders)
+    }
+
+    /// Consume the book, moving out its sells, cheapest first, like [`ReportingOrderBookApi::iter_sells`].
+    ///
+    /// Only each order's price is cloned. Held quantity isn't included.
+    pub fn into_sells(self) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>>
+    where
+        PriceT: Clone,
+    {
+        self.sells.into_iter().flat_map(into_orders)
+    }
+
+    /// Like [`OrderBookApi::query`], but borrows the order's quantity and price from the book.
+    pub fn query_ref(
+        &self,
+        id: OrderIdT,
+    ) -> Result<BuyOrSellRef<'_, QuantityT, PriceT>, NoSuchOrder>
+    where
+        OrderIdT: Eq + Hash + Debug,
+        PriceT: Debug,
+    {
+        let price_level = self.ids_to_price_level.get(&id).ok_or(NoSuchOrder)?;
+        let order = match price_level {
+            BuyOrSellAtPriceLevel::Buy(level) => {
+                find_quantity(&self.buys, level, &id).map(|quantity| BuyOrSell::Buy {
+                    quantity,
+                    unit_price: level,
+                })
+            }
+            BuyOrSellAtPriceLevel::Sell(level) => {
+                find_quantity(&self.sells, level, &id).map(|quantity| BuyOrSell::Sell {
+                    quantity,
+                    unit_price: level,
+                })
+            }
+        };
+        order.ok_or_else(|| self.stale_index(&id, price_level))
+    }
+
+    /// The order directly behind `id` at its price level, or [`None`] if it is last.
+    pub fn next_in_queue(&self, id: OrderIdT) -> Result<Option<OrderIdT>, NoSuchOrder>
+    where
+        OrderIdT: Eq + Hash + Debug,
+        PriceT: Debug,
+    {
+        let price_level = self.ids_to_price_level.get(&id).ok_or(NoSuchOrder)?;
+        let level = match price_level {
+            BuyOrSellAtPriceLevel::Buy(price) => self.buys.get(price),
+            BuyOrSellAtPriceLevel::Sell(price) => self.sells.get(price),
+        };
+        let mut queue = level.into_iter().flat_map(|level| level.iter());
+        match queue.position(|(it_id, _)| it_id == &id) {
+            Some(_) => Ok(queue.next().map(|(next, _)| next.clone())),
+            None => Err(self.stale_index(&id, price_level)),
+        }
+    }
+
+    /// Up to `n` of the most recent trades, most recent first.
+    ///
+    /// Only as many trades as were configured with [`Self::with_recent_trades_capacity`] are remembered.
+    pub fn recent_trades(&self, n: usize) -> Vec<Trade<QuantityT, PriceT, OrderIdT>>
+    where
+        PriceT: Clone,
+    {
+        self.recent_trades.iter().rev().take(n).cloned().collect()
+    }
+
+    /// Up to `n` of the most recent removals, most recent first.
+    ///
+    /// Each fill against a resting order is a removal, whether or not it leaves the book.
+    pub fn recent_removals(&self, n: usize) -> Vec<OrderRemoved<QuantityT, PriceT, OrderIdT>>
+    where
+        QuantityT: Clone,
+        PriceT: Clone,
+        OrderIdT: Clone,
+    {
+        self.removals.iter().rev().take(n).cloned().collect()
+    }
+
+    pub fn query_status(&self, id: &OrderIdT) -> OrderStatus
+    where
+        OrderIdT: Eq + Hash,
+    {
+        if self.ids_to_price_level.contains_key(id) {
+            return OrderStatus::Resting;
+        }
+        self.completed
+            .iter()
+            .rev()
+            .find_map(|(it_id, status)| match it_id == id {
+                true => Some(*status),
+                false => None,
+            })
+            .unwrap_or(OrderStatus::Unknown)
+    }
+
+    /// The makers `id` traded with when it entered the book, and how much with each, in the order they traded.
+    ///
+    /// Orders which trade in full on entry are never given an id, so aren't recorded.
+    /// Returns [`None`] if [`Self::query_status`] doesn't know `id`.
+    pub fn fills_of(&self, id: &OrderIdT) -> Option<Vec<(OrderIdT, QuantityT)>>
+    where
+        QuantityT: Clone,
+        OrderIdT: Clone + Eq + Hash,
+    {
+        match self.query_status(id) {
+            OrderStatus::Unknown => None,
+            _ => Some(self.counterparties.get(id).cloned().unwrap_or_default()),
+        }
+    }
+
+    /// How many resident orders an incoming order on `side` would trade with, without trading,
+    /// across every level up to `limit_price`.
+    pub fn impact_count(&self, side: Side, quantity: QuantityT, limit_price: &PriceT) -> usize
+    where
+        QuantityT: num::Zero + Ord + ops::Sub<Output = QuantityT>,
+    {
+        let levels: Box<dyn Iterator<Item = _>> = match side {
+            Side::Buy => Box::new(self.sells.range(..=limit_price).map(|(_, level)| level)),
+            Side::Sell => Box::new(self.buys.range(limit_price..).rev().map(|(_, level)| level)),
+        };
+        let mut remaining = quantity;
+        let mut count = 0;
+        for (_, resident_quantity) in levels.flat_map(|level| level.iter()) {
+            if remaining.is_zero() {
+                break;
+            }
+            count += 1;
+            if &remaining <= resident_quantity {
+                break;
+            }
+            remaining = remaining - resident_quantity.clone();
+        }
+        count
+    }
+
+    /// The total quantity of sells at or below `limit_price`.
+    ///
+    /// See [`Self::checked_available_to_buy`] if the total may overflow.
+    pub fn available_to_buy(&self, limit_price: &PriceT) -> QuantityT
+    where
+        QuantityT: num::Zero,
+    {
+        total_quantity(self.sells.range(..=limit_price))
+    }
+
+    /// The total quantity of buys at or above `limit_price`.
+    ///
+    /// See [`Self::checked_available_to_sell`] if the total may overflow.
+    pub fn available_to_sell(&self, limit_price: &PriceT) -> QuantityT
+    where
+        QuantityT: num::Zero,
+    {
+        total_quantity(self.buys.range(limit_price..))
+    }
+
+    /// Like [`Self::available_to_buy`], but returns [`None`] if the total overflows.
+    pub fn checked_available_to_buy(&self, limit_price: &PriceT) -> Option<QuantityT>
+    where
+        QuantityT: num::Zero + num::CheckedAdd,
+    {
+        checked_total_quantity(self.sells.range(..=limit_price))
+    }
+
+    /// Like [`Self::available_to_sell`], but returns [`None`] if the total overflows.
+    pub fn checked_available_to_sell(&self, limit_price: &PriceT) -> Option<QuantityT>
+    where
+        QuantityT: num::Zero + num::CheckedAdd,
+    {
+        checked_total_quantity(self.buys.range(limit_price..))
+    }
+
+    /// The quantity which must be bought for the best ask to be at or above `target_price`,
+    /// which is the total quantity of sells below it.
+    pub fn quantity_to_move_ask_to(&self, target_price: &PriceT) -> QuantityT
+    where
+        QuantityT: num::Zero,
+    {
+        total_quantity(self.sells.range(..target_price))
+    }
+
+    /// The quantity which must be sold for the best bid to be at or below `target_price`,
+    /// which is the total quantity of buys above it.
+    pub fn quantity_to_move_bid_to(&self, target_price: &PriceT) -> QuantityT
+    where
+        QuantityT: num::Zero,
+    {
+        total_quantity(
+            self.buys
+                .range((ops::Bound::Excluded(target_price), ops::Bound::Unbounded)),
+        )
+    }
+
+    /// The total quantity at each occupied price in `prices`, in ascending price order.
+    /// Empty if `prices` is, such as when its start is above its end.
+    pub fn levels_in_range(
+        &self,
+        side: Side,
+        prices: RangeInclusive<PriceT>,
+    ) -> Vec<(PriceT, QuantityT)>
+    where
+        PriceT: Clone,
+        QuantityT: num::Zero,
+    {
+        if prices.is_empty() {
+            return Vec::new();
+        }
+        let levels = self.levels(side);
+        levels
+            .range(prices)
+            .map(|(price, level)| (price.clone(), level_quantity(level)))
+            .collect()
+    }
+
+    /// The total quantity on `side` priced no more than `ticks` increments of `tick_size` worse than its best price.
+    /// Zero if `side` is empty.
+    ///
+    /// A window too wide for `PriceT` covers the whole side.
+    pub fn liquidity_within_ticks(&self, side: Side, ticks: usize, tick_size: PriceT) -> QuantityT
+    where
+        QuantityT: num::Zero + Clone,
+        PriceT: Clone + num::CheckedAdd + num::CheckedSub + num::CheckedMul + num::NumCast,
+    {
+        let width =
+            <PriceT as num::NumCast>::from(ticks).and_then(|ticks| tick_size.checked_mul(&ticks));
+        match side {
+            Side::Buy => {
+                let Some(best) = self.buys.max() else {
+                    return QuantityT::zero();
+                };
+                match width.and_then(|width| best.checked_sub(&width)) {
+                    Some(worst) => total_quantity(self.buys.range(worst..)),
+                    None => total_quantity(&self.buys),
+                }
+            }
+            Side::Sell => {
+                let Some(best) = self.sells.min() else {
+                    return QuantityT::zero();
+                };
+                match width.and_then(|width| best.checked_add(&width)) {
+                    Some(worst) => total_quantity(self.sells.range(..=worst)),
+                    None => total_quantity(&self.sells),
+                }
+            }
+        }
+    }
+
+    /// Every resting order as a JSON ladder, independent of the book's internal representation:
+    ///
+    /// ```json
+    /// {
+    ///   "bids": [{ "price": 10, "quantity": 2, "id": "..." }],
+    ///   "asks": [{ "price": 12, "quantity": 1, "id": "..." }]
+    /// }
+    /// ```
+    ///
+    /// Each side is in priority order.
+    #[cfg(feature = "serde")]
+    pub fn to_json_value(&self) -> serde_json::Value
+    where
+        QuantityT: serde::Serialize,
+        PriceT: serde::Serialize,
+        OrderIdT: serde::Serialize,
+    {
+        fn ladder<'a, QuantityT, PriceT, OrderIdT>(
+            levels: impl Iterator<Item = (&'a PriceT, &'a NonEmpty<VecDeque<(OrderIdT, QuantityT)>>)>,
+        ) -> serde_json::Value
+        where
+            QuantityT: serde::Serialize + 'a,
+            PriceT: serde::Serialize + 'a,
+            OrderIdT: serde::Serialize + 'a,
+        {
+            levels
+                .flat_map(|(price, level)| {
+                    level.iter().map(move |(id, quantity)| {
+                        serde_json::json!({ "price": price, "quantity": quantity, "id": id })
+                    })
+                })
+                .collect()
+        }
+        serde_json::json!({
+            "bids": ladder(self.buys.iter().rev()),
+            "asks": ladder(self.sells.iter()),
+        })
+    }
+
+    /// The total price improvement given to takers since [`Self::reset_session`],
+    /// counting each fill's spread once, so an order which sweeps several levels counts each of them.
+    pub fn session_spread_captured(&self) -> PriceT
+    where
+        PriceT: Clone + num::Zero,
+    {
+        self.session_spread_captured
+            .clone()
+            .unwrap_or_else(PriceT::zero)
+    }
+
+    /// How many times a resting order at `price` has been matched, on either side.
+    pub fn level_hit_count(&self, price: &PriceT) -> u64 {
+        self.level_hits.get(price).copied().unwrap_or_default()
+    }
+
+    /// Every price with a resting buy, most generous first.
+    pub fn bid_prices(&self) -> Vec<PriceT>
+    where
+        PriceT: Clone,
+    {
+        self.buys.keys().rev().cloned().collect()
+    }
+
+    /// Every price with a resting sell, cheapest first.
+    pub fn ask_prices(&self) -> Vec<PriceT>
+    where
+        PriceT: Clone,
+    {
+        self.sells.keys().cloned().collect()
+    }
+
+    pub fn lowest_bid_price(&self) -> Option<PriceT>
+    where
+        PriceT: Clone,
+    {
+        self.buys.min().cloned()
+    }
+
+    /// Includes any placeholder from [`Self::with_top_of_book_hint`].
+    pub fn highest_bid_price(&self) -> Option<PriceT>
+    where
+        PriceT: Clone,
+    {
+        self.buys
+            .max()
+            .into_iter()
+            .chain(&self.bid_hint)
+            .max()
+            .cloned()
+    }
+
+    /// Includes any placeholder from [`Self::with_top_of_book_hint`].
+    pub fn lowest_ask_price(&self) -> Option<PriceT>
+    where
+        PriceT: Clone,
+    {
+        self.sells
+            .min()
+            .into_iter()
+            .chain(&self.ask_hint)
+            .min()
+            .cloned()
+    }
+
+    pub fn highest_ask_price(&self) -> Option<PriceT>
+    where
+        PriceT: Clone,
+    {
+        self.sells.max().cloned()
+    }
+
+    /// Matching never leaves the book locked or crossed, but resting orders without matching can:
+    /// while the book is [halted for an auction](Self::halt_for_auction), or when it is collected
+    /// [from an iterator](FromIterator).
+    /// Such crosses last until the book is [resumed](Self::resume) or they are [resolved](Self::resolve_crosses).
+    pub fn book_state(&self) -> BookState {
+        match (self.buys.max(), self.sells.min()) {
+            (Some(best_bid), Some(best_ask)) => match best_bid.cmp(best_ask) {
+                Ordering::Less => BookState::Normal,
+                Ordering::Equal => BookState::Locked,
+                Ordering::Greater => BookState::Crossed,
+            },
+            _ => BookState::Normal,
+        }
+    }
+
+    /// A hash of the price and total quantity of the best `depth` levels on each side,
+    /// so two books with the same levels have the same checksum, regardless of how they got there.
+    ///
+    /// This is the 64-bit FNV-1a hash of the following, each value in its [`Wire`] encoding,
+    /// so that it may be reproduced outside this crate:
+    /// - the number of bid levels which follow, as a `u64`
+    /// - the price then total quantity of each of those bids, best first
+    /// - the number of ask levels which follow, as a `u64`
+    /// - the price then total quantity of each of those asks, best first
+    pub fn checksum(&self, depth: usize) -> u64
+    where
+        QuantityT: num::Zero + Wire,
+        PriceT: Wire,
+    {
+        let bids = self
+            .buys
+            .iter()
+            .rev()
+            .take(depth)
+            .map(|(price, level)| (price, level_quantity(level)))
+            .collect::<Vec<_>>();
+        let asks = self
+            .sells
+            .iter()
+            .take(depth)
+            .map(|(price, level)| (price, level_quantity(level)))
+            .collect::<Vec<_>>();
+        let mut bytes = Vec::new();
+        for side in [bids, asks] {
+            (side.len() as u64).write(&mut bytes);
+            for (price, quantity) in side {
+                price.write(&mut bytes);
+                quantity.write(&mut bytes);
+            }
+        }
+        let mut hasher = Fnv1a::default();
+        hasher.write(&bytes);
+        hasher.finish()
+    }
+
+    /// Like a depth snapshot, but with prices rounded down to a multiple of `bucket_width`,
+    /// and the quantity of levels which share a bucket summed.
+    ///
+    /// Each side has at most `max_buckets` buckets, however many levels they hold.
+    pub fn depth_conflated(
+        &self,
+        bucket_width: Positive<PriceT>,
+        max_buckets: usize,
+    ) -> DepthSnapshot<QuantityT, PriceT>
+    where
+        QuantityT: num::Zero,
+        PriceT: Clone + ops::Div<Output = PriceT> + ops::Mul<Output = PriceT>,
+    {
+        let bucket_width = bucket_width.into_inner();
+        DepthSnapshot {
+            bids: conflate(self.buys.iter().rev(), &bucket_width, max_buckets),
+            asks: conflate(self.sells.iter(), &bucket_width, max_buckets),
+        }
+    }
+
+    /// Up to `n` of the largest orders on `side`, largest first, regardless of price.
+    /// Orders of equal size are in priority order.
+    pub fn largest_orders(&self, side: Side, n: usize) -> Vec<Order<QuantityT, PriceT, OrderIdT>>
+    where
+        QuantityT: Ord,
+        PriceT: Clone,
+    {
+        let mut orders = match side {
+            Side::Buy => collect_orders(self.buys.iter().rev()),
+            Side::Sell => collect_orders(self.sells.iter()),
+        };
+        orders.sort_by(|left, right| right.quantity.cmp(&left.quantity));
+        orders.truncate(n);
+        orders
+    }
+
+    /// The price and total quantity of the best `levels` levels on each side.
+    ///
+    /// See [`Self::checked_depth`] if a level's total may overflow.
+    pub fn depth(&self, levels: usize) -> DepthSnapshot<QuantityT, PriceT>
+    where
+        QuantityT: num::Zero,
+        PriceT: Clone,
+    {
+        DepthSnapshot {
+            bids: self
+                .buys
+                .iter()
+                .rev()
+                .take(levels)
+                .map(|(price, level)| (price.clone(), level_quantity(level)))
+                .collect(),
+            asks: self
+                .sells
+                .iter()
+                .take(levels)
+                .map(|(price, level)| (price.clone(), level_quantity(level)))
+                .collect(),
+        }
+    }
+
+    /// Like [`Self::depth`], but returns [`None`] if any level's total overflows.
+    pub fn checked_depth(&self, levels: usize) -> Option<DepthSnapshot<QuantityT, PriceT>>
+    where
+        QuantityT: num::Zero + num::CheckedAdd,
+        PriceT: Clone,
+    {
+        Some(DepthSnapshot {
+            bids: self
+                .buys
+                .iter()
+                .rev()
+                .take(levels)
+                .map(|(price, level)| Some((price.clone(), checked_level_quantity(level)?)))
+                .collect::<Option<_>>()?,
+            asks: self
+                .sells
+                .iter()
+                .take(levels)
+                .map(|(price, level)| Some((price.clone(), checked_level_quantity(level)?)))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// The best ask less the best bid.
+    /// Returns [`None`] if either side is empty, or if the book is [crossed](BookState::Crossed),
+    /// so that the spread would be negative.
+    pub fn spread(&self) -> Option<PriceT>
+    where
+        PriceT: Clone + ops::Sub<Output = PriceT>,
+    {
+        let (best_bid, best_ask) = (self.buys.max()?, self.sells.min()?);
+        (best_ask >= best_bid).then(|| best_ask.clone() - best_bid.clone())
+    }
+
+    /// Halfway between the best bid and the best ask, rounded as [`ops::Div`] rounds.
+    /// Returns [`None`] if either side is empty, or if the book is [crossed](BookState::Crossed).
+    pub fn mid_price(&self) -> Option<PriceT>
+    where
+        PriceT: Clone
+            + num::One
+            + ops::Add<Output = PriceT>
+            + ops::Sub<Output = PriceT>
+            + ops::Div<Output = PriceT>,
+    {
+        let best_bid = self.buys.max()?.clone();
+        Some(best_bid.clone() + self.spread()? / (PriceT::one() + PriceT::one()))
+    }
+
+    /// [`Self::depth`], [`Self::spread`] and [`Self::mid_price`] together.
+    pub fn quote(&self, levels: usize) -> Quote<QuantityT, PriceT>
+    where
+        QuantityT: num::Zero,
+        PriceT: Clone
+            + num::One
+            + ops::Add<Output = PriceT>
+            + ops::Sub<Output = PriceT>
+            + ops::Div<Output = PriceT>,
+    {
+        let DepthSnapshot { bids, asks } = self.depth(levels);
+        Quote {
+            bids,
+            asks,
+            spread: self.spread(),
+            mid_price: self.mid_price(),
+        }
+    }
+
+    /// The best bid and best ask, with their quantities and order counts.
+    /// Returns [`None`] if either side is empty.
+    pub fn touch(&self) -> Option<Touch<QuantityT, PriceT>>
+    where
+        QuantityT: num::Zero + Clone,
+        PriceT: Clone,
+    {
+        let (bid_price, bids) = self.buys.last_key_value()?;
+        let (ask_price, asks) = self.sells.first_key_value()?;
+        Some(Touch {
+            bid_price: bid_price.clone(),
+            bid_quantity: level_quantity(bids),
+            bid_orders: bids.len(),
+            ask_price: ask_price.clone(),
+            ask_quantity: level_quantity(asks),
+            ask_orders: asks.len(),
+        })
+    }
+
+    /// The single price at which the most quantity would trade if the book were uncrossed now,
+    /// with that quantity.
+    /// Ties go to the lowest price, as with [`ClearingRule::MaxVolumeThenLowestPrice`].
+    ///
+    /// Returns [`None`] if the book isn't locked or crossed.
+    pub fn indicative_price(&self) -> Option<(PriceT, QuantityT)>
+    where
+        QuantityT: num::Zero + Clone + Ord + ops::Sub<Output = QuantityT>,
+        PriceT: Clone + ops::Sub<Output = PriceT>,
+    {
+        self.indicative_price_with(ClearingRule::MaxVolumeThenLowestPrice)
+    }
+
+    /// Like [`Self::indicative_price`], but ties are broken by `rule`.
+    pub fn indicative_price_with(&self, rule: ClearingRule<PriceT>) -> Option<(PriceT, QuantityT)>
+    where
+        QuantityT: num::Zero + Clone + Ord + ops::Sub<Output = QuantityT>,
+        PriceT: Clone + ops::Sub<Output = PriceT>,
+    {
+        fn distance<T: Ord + ops::Sub<Output = T>>(left: T, right: T) -> T {
+            match left > right {
+                true => left - right,
+                false => right - left,
+            }
+        }
+
+        let mut candidates = self
+            .buys
+            .keys()
+            .chain(self.sells.keys())
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.dedup();
+        // the quantity on offer to buyers and bid by sellers at each price, lowest price first
+        let candidates = candidates
+            .into_iter()
+            .map(|price| {
+                let offered = self.available_to_buy(price);
+                let bid = self.available_to_sell(price);
+                (price, offered, bid)
+            })
+            .collect::<Vec<_>>();
+        let volume = |offered: &QuantityT, bid: &QuantityT| offered.clone().min(bid.clone());
+        let max_volume = candidates
+            .iter()
+            .map(|(_, offered, bid)| volume(offered, bid))
+            .max()
+            .filter(|max_volume| !max_volume.is_zero())?;
+        let mut tied = candidates
+            .into_iter()
+            .filter(|(_, offered, bid)| volume(offered, bid) == max_volume);
+        // `min_by_key` keeps the first, so the lowest, of equally good prices
+        let (price, _, _) = match rule {
+            ClearingRule::MaxVolumeThenLowestPrice => tied.next(),
+            ClearingRule::MaxVolumeThenMinImbalance => {
+                tied.min_by_key(|(_, offered, bid)| distance(offered.clone(), bid.clone()))
+            }
+            ClearingRule::MaxVolumeThenReferenceClosest(reference) => {
+                tied.min_by_key(|(price, _, _)| distance((*price).clone(), reference.clone()))
+            }
+        }?;
+        Some((price.clone(), max_volume))
+    }
+
+    /// The mean price of resting buys, weighted by quantity.
+    /// Returns [`None`] if there are no buys.
+    pub fn avg_bid_price(&self) -> Option<PriceT>
+    where
+        QuantityT: num::Zero,
+        PriceT: Clone
+            + num::Zero
+            + ops::Mul<QuantityT, Output = PriceT>
+            + ops::Div<QuantityT, Output = PriceT>,
+    {
+        weighted_average_price(&self.buys)
+    }
+
+    /// The mean price of resting sells, weighted by quantity.
+    /// Returns [`None`] if there are no sells.
+    pub fn avg_ask_price(&self) -> Option<PriceT>
+    where
+        QuantityT: num::Zero,
+        PriceT: Clone
+            + num::Zero
+            + ops::Mul<QuantityT, Output = PriceT>
+            + ops::Div<QuantityT, Output = PriceT>,
+    {
+        weighted_average_price(&self.sells)
+    }
+
+    /// The sum of `price * quantity` over resting buys.
+    ///
+    /// `NotionalT` may be wider than `PriceT` and `QuantityT`, so that the products don't overflow.
+    /// This walks the book, unless [kept](Self::with_notional_totals) in `NotionalT`.
+    pub fn total_buy_notional<NotionalT>(&self) -> NotionalT
+    where
+        QuantityT: Clone + Into<NotionalT>,
+        PriceT: Clone + Into<NotionalT> + 'static,
+        NotionalT: Clone + num::Zero + ops::Mul<Output = NotionalT> + 'static,
+    {
+        if let Some(totals) = self.notional_totals::<NotionalT>() {
+            return totals.total(Side::Buy);
+        }
+        self.buys
+            .iter()
+            .fold(NotionalT::zero(), |total, (price, level)| {
+                total + level_notional(price, level)
+            })
+    }
+
+    /// The sum of `price * quantity` over resting sells.
+    ///
+    /// `NotionalT` may be wider than `PriceT` and `QuantityT`, so that the products don't overflow.
+    /// This walks the book, unless [kept](Self::with_notional_totals) in `NotionalT`.
+    pub fn total_sell_notional<NotionalT>(&self) -> NotionalT
+    where
+        QuantityT: Clone + Into<NotionalT>,
+        PriceT: Clone + Into<NotionalT> + 'static,
+        NotionalT: Clone + num::Zero + ops::Mul<Output = NotionalT> + 'static,
+    {
+        if let Some(totals) = self.notional_totals::<NotionalT>() {
+            return totals.total(Side::Sell);
+        }
+        self.sells
+            .iter()
+            .fold(NotionalT::zero(), |total, (price, level)| {
+                total + level_notional(price, level)
+            })
+    }
+
+    /// The sum of `price * quantity` over orders resting at `price`, zero if there are none.
+    pub fn notional_at_price<NotionalT>(&self, side: Side, price: &PriceT) -> NotionalT
+    where
+        QuantityT: Clone + Into<NotionalT>,
+        PriceT: Clone + Ord + Into<NotionalT> + 'static,
+        NotionalT: Clone + num::Zero + ops::Mul<Output = NotionalT> + 'static,
+    {
+        if let Some(totals) = self.notional_totals::<NotionalT>() {
+            return totals.at_price(side, price);
+        }
+        let level = self.levels(side).get(price);
+        level.map_or_else(NotionalT::zero, |level| level_notional(price, level))
+    }
+}
+
+fn into_orders<QuantityT, PriceT, OrderIdT>(
+    (unit_price, level): (PriceT, NonEmpty<VecDeque<(OrderIdT, QuantityT)>>),
+) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>>
+where
+    PriceT: Clone,
+{
+    level.into_iter().map(move |(id, quantity)| Order {
+        quantity,
+        unit_price: unit_price.clone(),
+        id,
+    })
+}
+
+fn level_notional<QuantityT, PriceT, OrderIdT, NotionalT>(
+    price: &PriceT,
+    level: &NonEmpty<VecDeque<(OrderIdT, QuantityT)>>,
+) -> NotionalT
+where
+    QuantityT: Clone + Into<NotionalT>,
+    PriceT: Clone + Into<NotionalT>,
+    NotionalT: num::Zero + ops::Mul<Output = NotionalT>,
+{
+    level
+        .iter()
+        .fold(NotionalT::zero(), |total, (_, quantity)| {
+            total + price.clone().into() * quantity.clone().into()
+        })
+}
+
+fn weighted_average_price<QuantityT, PriceT, OrderIdT>(
+    levels: &BTreeMap<PriceT, NonEmpty<VecDeque<(OrderIdT, QuantityT)>>>,
+) -> Option<PriceT>
+where
+    QuantityT: num::Zero + Clone,
+    PriceT: Clone
+        + num::Zero
+        + ops::Mul<QuantityT, Output = PriceT>
+        + ops::Div<QuantityT, Output = PriceT>,
+{
+    if levels.is_empty() {
+        return None;
+    }
+    let (notional, quantity) = levels.iter().fold(
+        (PriceT::zero(), QuantityT::zero()),
+        |(notional, quantity), (price, level)| {
+            let level_quantity = level_quantity(level);
+            (
+                notional + price.clone() * level_quantity.clone(),
+                quantity + level_quantity,
+            )
+        },
+    );
+    Some(notional / quantity)
+}
+
+/// Sum `levels` into the first `max_buckets` buckets of `bucket_width`, in the order they are given.
+fn conflate<'a, QuantityT, PriceT, OrderIdT>(
+    levels: impl IntoIterator<Item = (&'a PriceT, &'a NonEmpty<VecDeque<(OrderIdT, QuantityT)>>)>,
+    bucket_width: &PriceT,
+    max_buckets: usize,
+) -> Vec<(PriceT, QuantityT)>
+where
+    QuantityT: num::Zero + Clone + 'a,
+    PriceT: Clone + PartialEq + ops::Div<Output = PriceT> + ops::Mul<Output = PriceT> + 'a,
+    OrderIdT: 'a,
+{
+    let mut buckets = Vec::<(PriceT, QuantityT)>::new();
+    for (price, level) in levels {
+        let bucket = (price.clone() / bucket_width.clone()) * bucket_width.clone();
+        let full = buckets.len() == max_buckets;
+        match buckets.last_mut() {
+            Some((last, quantity)) if *last == bucket => {
+                *quantity = quantity.clone() + level_quantity(level)
+            }
+            _ if full => break,
+            _ => buckets.push((bucket, level_quantity(level))),
+        }
+    }
+    buckets
+}
+
+fn collect_orders<'a, QuantityT, PriceT, OrderIdT>(
+    levels: impl IntoIterator<Item = (&'a PriceT, &'a NonEmpty<VecDeque<(OrderIdT, QuantityT)>>)>,
+) -> Vec<Order<QuantityT, PriceT, OrderIdT>>
+where
+    QuantityT: Clone + 'a,
+    PriceT: Clone + 'a,
+    OrderIdT: Clone + 'a,
+{
+    levels
+        .into_iter()
+        .flat_map(|(price, level)| {
+            level.iter().map(|(id, quantity)| Order {
+                quantity: quantity.clone(),
+                unit_price: price.clone(),
+                id: id.clone(),
+            })
+        })
+        .collect()
+}
+
+fn find_quantity<'a, QuantityT, PriceT, OrderIdT>(
+    levels: &'a BTreeMap<PriceT, NonEmpty<VecDeque<(OrderIdT, QuantityT)>>>,
+    unit_price: &PriceT,
+    id: &OrderIdT,
+) -> Option<&'a QuantityT>
+where
+    PriceT: Ord,
+    OrderIdT: PartialEq,
+{
+    levels
+        .get(unit_price)?
+        .iter()
+        .find_map(|(it_id, quantity)| match it_id == id {
+            true => Some(quantity),
+            false => None,
+        })
+}
+
+fn total_quantity<'a, QuantityT, PriceT, OrderIdT>(
+    levels: impl IntoIterator<Item = (&'a PriceT, &'a NonEmpty<VecDeque<(OrderIdT, QuantityT)>>)>,
+) -> QuantityT
+where
+    QuantityT: num::Zero + Clone + 'a,
+    PriceT: 'a,
+    OrderIdT: 'a,
+{
+    levels
+        .into_iter()
+        .fold(QuantityT::zero(), |total, (_, level)| {
+            total + level_quantity(level)
+        })
+}
+
+fn checked_total_quantity<'a, QuantityT, PriceT, OrderIdT>(
+    levels: impl IntoIterator<Item = (&'a PriceT, &'a NonEmpty<VecDeque<(OrderIdT, QuantityT)>>)>,
+) -> Option<QuantityT>
+where
+    QuantityT: num::Zero + num::CheckedAdd + Clone + 'a,
+    PriceT: 'a,
+    OrderIdT: 'a,
+{
+    levels
+        .into_iter()
+        .try_fold(QuantityT::zero(), |total, (_, level)| {
+            total.checked_add(&checked_level_quantity(level)?)
+        })
+}
+
+fn checked_level_quantity<QuantityT, OrderIdT>(
+    level: &NonEmpty<VecDeque<(OrderIdT, QuantityT)>>,
+) -> Option<QuantityT>
+where
+    QuantityT: num::Zero + num::CheckedAdd,
+{
+    level
+        .iter()
+        .try_fold(QuantityT::zero(), |total, (_, quantity)| {
+            total.checked_add(quantity)
+        })
+}
+
+fn level_quantity<QuantityT, OrderIdT>(
+    level: &NonEmpty<VecDeque<(OrderIdT, QuantityT)>>,
+) -> QuantityT
+where
+    QuantityT: num::Zero + Clone,
+{
+    level
+        .iter()
+        .fold(QuantityT::zero(), |total, (_, quantity)| {
+            total + quantity.clone()
+        })
+}
+
+impl<QuantityT, PriceT> PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Clone,
+    PriceT: Clone + Ord,
+{
+    /// Add an order to the back of its price level, without matching it.
+    fn rest(&mut self, side: Side, quantity: QuantityT, unit_price: PriceT) -> uuid::Uuid {
+        let id = uuid::Uuid::new_v4();
+        if self.ids_to_price_level.contains_key(&id) {
+            panic!("uuid collision")
+        }
+        self.insert_resting(id, side, quantity, unit_price);
+        id
+    }
+
+    /// Like [`Self::rest`], keeping the order's minimum quantity, if it has one.
+    fn rest_limited(
+        &mut self,
+        side: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+        limits: TakerLimits<QuantityT>,
+    ) -> uuid::Uuid {
+        let id = self.rest(side, quantity, unit_price);
+        if let Some(min_quantity) = limits.min_quantity {
+            self.min_quantities.insert(id, min_quantity);
+        }
+        id
+    }
+
+    /// Like [`Self::rest`], with an `id` which isn't in the book.
+    fn insert_resting(
+        &mut self,
+        id: uuid::Uuid,
+        side: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+    ) {
+        match side {
+            Side::Buy
+                if self
+                    .bid_hint
+                    .as_ref()
+                    .is_some_and(|hint| hint <= &unit_price) =>
+            {
+                self.bid_hint = None
+            }
+            Side::Sell
+                if self
+                    .ask_hint
+                    .as_ref()
+                    .is_some_and(|hint| hint >= &unit_price) =>
+            {
+                self.ask_hint = None
+            }
+            _ => {}
+        }
+        self.notional_entered(side, &unit_price, &quantity);
+        let (levels, price_level) = match side {
+            Side::Buy => (
+                &mut self.buys,
+                BuyOrSellAtPriceLevel::Buy(unit_price.clone()),
+            ),
+            Side::Sell => (
+                &mut self.sells,
+                BuyOrSellAtPriceLevel::Sell(unit_price.clone()),
+            ),
+        };
+        levels
+            .entry(unit_price)
+            .and_modify(|level| level.push_back((id, quantity.clone())))
+            .or_insert_with(|| NonEmpty::vecdeque((id, quantity)));
+        self.ids_to_price_level.insert(id, price_level);
+    }
+
+    /// Take what `removal` describes out of the book, and report it.
+    ///
+    /// Every path which takes quantity out of the book goes through here.
+    /// An order which leaves is taken out of the index and out of its level, if it is still queued there,
+    /// and if it was cancelled or evicted, its holds are dropped and reported as part of its quantity.
+    fn remove_order(&mut self, mut removal: OrderRemoved<QuantityT, PriceT, uuid::Uuid>)
+    where
+        QuantityT: num::Zero,
+    {
+        let status = match removal.reason {
+            RemovalReason::Filled => Some(OrderStatus::Filled),
+            RemovalReason::Cancelled | RemovalReason::Evicted => Some(OrderStatus::Cancelled),
+            // the order may still be resting, and was recorded when it left if not
+            RemovalReason::PartiallyFilled | RemovalReason::Committed => None,
+        };
+        if let Some(status) = status {
+            self.ids_to_price_level.remove(&removal.id);
+            // walks and trims take orders out of their levels, and account for them, before they are reported here
+            let queued = find_quantity(self.levels(removal.side), &removal.price, &removal.id);
+            if let Some(quantity) = queued.cloned() {
+                self.notional_left(removal.side, &removal.price, &quantity);
+                let levels = self.levels_mut(removal.side);
+                let level = levels.remove(&removal.price).expect("order was just found");
+                if let (Some(level), _) = level.pop_once_by(|(id, _)| *id == removal.id) {
+                    levels.insert_uncontended(removal.price.clone(), level)
+                }
+            }
+            if status == OrderStatus::Cancelled {
+                removal.quantity = removal.quantity + self.drop_holds(&removal.id);
+            }
+            self.min_quantities.remove(&removal.id);
+            self.record_completed(removal.id, status);
+        }
+        if self.removals_capacity == 0 {
+            return;
+        }
+        if self.removals.len() == self.removals_capacity {
+            self.removals.pop_front();
+        }
+        self.removals.push_back(removal)
+    }
+
+    /// Forget any [holds](Self::hold) on `id`, which is leaving the book other than by being filled,
+    /// returning how much they held.
+    fn drop_holds(&mut self, id: &uuid::Uuid) -> QuantityT
+    where
+        QuantityT: num::Zero,
+    {
+        let tokens = self
+            .holds
+            .iter()
+            .filter(|(_, hold)| hold.id == *id)
+            .map(|(token, _)| *token)
+            .collect::<Vec<_>>();
+        tokens
+            .into_iter()
+            .filter_map(|token| self.holds.remove(&token))
+            .fold(QuantityT::zero(), |total, hold| total + hold.quantity)
+    }
+
+    /// Remove every level beyond the best `levels` on each side, returning the removed orders:
+    /// bids then asks, each in priority order.
+    ///
+    /// Any holds on the removed orders are dropped, and reported as part of their removal.
+    pub fn trim_to_levels(&mut self, levels: usize) -> Vec<Order<QuantityT, PriceT, uuid::Uuid>>
+    where
+        QuantityT: num::Zero,
+    {
+        let mut evicted_bids = Vec::new();
+        while self.buys.len() > levels {
+            evicted_bids.push(self.buys.pop_first().expect("more than zero levels"));
+        }
+        let mut evicted_asks = Vec::new();
+        while self.sells.len() > levels {
+            evicted_asks.push(self.sells.pop_last().expect("more than zero levels"));
+        }
+        let evicted_bids = collect_orders(
+            evicted_bids
+                .iter()
+                .rev()
+                .map(|(price, level)| (price, level)),
+        );
+        let evicted_asks = collect_orders(
+            evicted_asks
+                .iter()
+                .rev()
+                .map(|(price, level)| (price, level)),
+        );
+        let evicted_bids = evicted_bids.into_iter().map(|order| (Side::Buy, order));
+        let evicted_asks = evicted_asks.into_iter().map(|order| (Side::Sell, order));
+        let mut evicted = Vec::new();
+        for (side, order) in evicted_bids.chain(evicted_asks) {
+            self.notional_left(side, &order.unit_price, &order.quantity);
+            self.remove_order(OrderRemoved {
+                id: order.id,
+                reason: RemovalReason::Evicted,
+                side,
+                price: order.unit_price.clone(),
+                quantity: order.quantity.clone(),
+            });
+            evicted.push(order);
+        }
+        if !evicted.is_empty() {
+            self.next_sequence();
+        }
+        evicted
+    }
+
+    /// Adjust the quantity of each order at `price`, in priority order.
+    ///
+    /// Orders whose quantity is set to zero are removed, as if cancelled,
+    /// and the level is removed if none remain.
+    pub fn for_each_order_at_mut(
+        &mut self,
+        side: Side,
+        price: &PriceT,
+        mut f: impl FnMut(uuid::Uuid, &mut QuantityT),
+    ) where
+        QuantityT: num::Zero,
+    {
+        let levels = self.levels_mut(side);
+        let Some((price, mut level)) = levels.remove_entry(price) else {
+            return;
+        };
+        let mut changed = Vec::new();
+        for (id, quantity) in level.iter_mut() {
+            let before = quantity.clone();
+            f(*id, quantity);
+            changed.push((*id, before, quantity.clone()));
+        }
+        if let Some(level) = level.retain(|(_, quantity)| !quantity.is_zero()) {
+            levels.insert_uncontended(price.clone(), level)
+        }
+        for (id, before, after) in changed {
+            self.notional_left(side, &price, &before);
+            self.notional_entered(side, &price, &after);
+            // an emptied order is reported with the quantity it had before `f` zeroed it
+            if after.is_zero() {
+                self.remove_order(OrderRemoved {
+                    id,
+                    reason: RemovalReason::Cancelled,
+                    side,
+                    price: price.clone(),
+                    quantity: before,
+                });
+            }
+        }
+        self.next_sequence();
+    }
+
+    /// Start a new session for [`Self::session_spread_captured`].
+    pub fn reset_session(&mut self) {
+        self.session_spread_captured = None;
+    }
+
+    fn record_trades(
+        &mut self,
+        sequence: u64,
+        taker_side: Side,
+        fills: &[FillReport<QuantityT, PriceT, uuid::Uuid>],
+    ) {
+        for fill in fills {
+            self.last_trade_price = Some(fill.unit_price.clone());
+            let trade = Trade {
+                sequence,
+                taker_side,
+                maker: fill.maker,
+                unit_price: fill.unit_price.clone(),
+                quantity: fill.quantity.clone(),
+            };
+            #[cfg(feature = "serde")]
+            self.log_trade(&trade);
+            if self.recent_trades_capacity == 0 {
+                continue;
+            }
+            if self.recent_trades.len() == self.recent_trades_capacity {
+                self.recent_trades.pop_front();
+            }
+            self.recent_trades.push_back(trade)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn log_trade(&mut self, trade: &Trade<QuantityT, PriceT, uuid::Uuid>) {
+        let Some(trade_log) = &mut self.trade_log else {
+            return;
+        };
+        if trade_log.error.is_some() {
+            return;
+        }
+        let mut write = trade_log
+            .write
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Err(error) = write(trade) {
+            tracing::error!(%error, "failed to write to the trade log");
+            match trade_log.error_policy {
+                TradeLogErrorPolicy::Panic => panic!("failed to write to the trade log: {error}"),
+                TradeLogErrorPolicy::Stop => {
+                    drop(write);
+                    trade_log.error = Some(std::sync::Arc::new(error))
+                }
+            }
+        }
+    }
+
+    /// Report what `fills` against an incoming order on `taker` took from each maker,
+    /// forgetting those they fully executed.
+    fn retire_filled_makers(
+        &mut self,
+        taker: Side,
+        fills: &[FillReport<QuantityT, PriceT, uuid::Uuid>],
+    ) where
+        QuantityT: num::Zero,
+    {
+        let side = match taker {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        for fill in fills {
+            let reason = match fill.maker_remaining.is_zero() {
+                true => RemovalReason::Filled,
+                false => RemovalReason::PartiallyFilled,
+            };
+            self.notional_left(side, &fill.unit_price, &fill.quantity);
+            self.remove_order(OrderRemoved {
+                id: fill.maker,
+                reason,
+                side,
+                price: fill.unit_price.clone(),
+                quantity: fill.quantity.clone(),
+            });
+        }
+    }
+}
+
+/// How [`PriceLevelBTreeOrderBook::indicative_price_with`] chooses between prices at which the same,
+/// greatest quantity would trade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClearingRule<PriceT> {
+    /// The lowest such price.
+    MaxVolumeThenLowestPrice,
+    /// The price which leaves the least quantity unmatched, then the lowest.
+    MaxVolumeThenMinImbalance,
+    /// The price closest to the given reference, such as the last trade, then the lowest.
+    MaxVolumeThenReferenceClosest(PriceT),
+}
+
+/// How the best bid compares to the best ask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BookState {
+    /// The best bid is below the best ask, or either side is empty.
+    Normal,
+    /// The best bid is equal to the best ask.
+    Locked,
+    /// The best bid is above the best ask.
+    Crossed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum InvalidOrderBook {
+    #[error("order {0} is indexed, but not present at its price level")]
+    StaleIndex(uuid::Uuid),
+    #[error("order {0} is present at a price level, but not indexed there")]
+    Unindexed(uuid::Uuid),
+    #[error("order {0} is present more than once")]
+    Duplicate(uuid::Uuid),
+    #[error("order {0} has no quantity remaining")]
+    NoQuantity(uuid::Uuid),
+    #[error("order {0} has a minimum quantity, but isn't in the book")]
+    StaleMinimum(uuid::Uuid),
+    #[error("the best bid is not below the best ask")]
+    Crossed,
+    #[error("the notional totals don't match the resting orders")]
+    NotionalDrift,
+}
+
+impl<QuantityT, PriceT> PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Wire + Clone + num::Zero,
+    PriceT: Wire + Clone + Ord,
+{
+    /// The resting orders, in a compact binary layout:
+    /// - The number of bid levels, then each bid level, most generous first.
+    /// - The number of ask levels, then each ask level, cheapest first.
+    ///
+    /// Each level is its price, then the number of orders, then each order's id and quantity, in priority order.
+    /// Counts are `usize`, and every value is encoded by [`Wire`].
+    ///
+    /// Only the orders are encoded, not their minimum quantities, nor the book's configuration or history.
+    pub fn encode(&self) -> Vec<u8> {
+        fn side<'a, QuantityT: Wire + 'a, PriceT: Wire + 'a>(
+            bytes: &mut Vec<u8>,
+            levels: impl ExactSizeIterator<
+                Item = (&'a PriceT, &'a NonEmpty<VecDeque<(uuid::Uuid, QuantityT)>>),
+            >,
+        ) {
+            levels.len().write(bytes);
+            for (price, level) in levels {
+                price.write(bytes);
+                level.iter().len().write(bytes);
+                for (id, quantity) in level.iter() {
+                    id.write(bytes);
+                    quantity.write(bytes);
+                }
+            }
+        }
+        let mut bytes = Vec::new();
+        side(&mut bytes, self.buys.iter().rev());
+        side(&mut bytes, self.sells.iter());
+        bytes
+    }
+
+    /// Rebuild a book from the output of [`Self::encode`], with default configuration.
+    ///
+    /// A default book is open, so a crossed book (such as one collecting orders for an auction) is refused.
+    pub fn decode(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut order_book = Self::default();
+        for side in [Side::Buy, Side::Sell] {
+            let mut previous = None::<PriceT>;
+            for _ in 0..usize::read(&mut bytes)? {
+                let price = PriceT::read(&mut bytes)?;
+                let in_order = previous.is_none_or(|previous| match side {
+                    Side::Buy => price < previous,
+                    Side::Sell => price > previous,
+                });
+                if !in_order {
+                    return Err(DecodeError::BadOrdering);
+                }
+                let orders = usize::read(&mut bytes)?;
+                if orders == 0 {
+                    return Err(DecodeError::EmptyLevel);
+                }
+                for _ in 0..orders {
+                    let id = uuid::Uuid::read(&mut bytes)?;
+                    let quantity = QuantityT::read(&mut bytes)?;
+                    if quantity.is_zero() {
+                        return Err(DecodeError::ZeroQuantity);
+                    }
+                    if order_book.ids_to_price_level.contains_key(&id) {
+                        return Err(DecodeError::DuplicateId);
+                    }
+                    order_book.insert_resting(id, side, quantity, price.clone());
+                }
+                previous = Some(price);
+            }
+        }
+        if !bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        match (order_book.buys.max(), order_book.sells.min()) {
+            (Some(bid), Some(ask)) if bid >= ask => Err(DecodeError::Crossed),
+            _ => Ok(order_book),
+        }
+    }
+}
+
+impl<QuantityT, PriceT> PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: num::Zero,
+    PriceT: Ord,
+{
+    /// Check the internal consistency of the book.
+    /// A book which fails validation is a bug.
+    pub fn validate(&self) -> Result<(), InvalidOrderBook> {
+        for (id, price_level) in &self.ids_to_price_level {
+            let level = match price_level {
+                BuyOrSellAtPriceLevel::Buy(price) => self.buys.get(price),
+                BuyOrSellAtPriceLevel::Sell(price) => self.sells.get(price),
+            };
+            if !level.is_some_and(|level| level.iter().any(|(it_id, _)| it_id == id)) {
+                return Err(InvalidOrderBook::StaleIndex(*id));
+            }
+        }
+        if let Some(id) = self
+            .min_quantities
+            .keys()
+            .find(|id| !self.ids_to_price_level.contains_key(id))
+        {
+            return Err(InvalidOrderBook::StaleMinimum(*id));
+        }
+
+        let resident_buys = self.buys.iter().flat_map(|(price, level)| {
+            level
+                .iter()
+                .map(move |order| (BuyOrSellAtPriceLevel::Buy(price), order))
+        });
+        let resident_sells = self.sells.iter().flat_map(|(price, level)| {
+            level
+                .iter()
+                .map(move |order| (BuyOrSellAtPriceLevel::Sell(price), order))
+        });
+        let mut seen = HashSet::new();
+        for (price_level, (id, quantity)) in resident_buys.chain(resident_sells) {
+            let indexed = match (price_level, self.ids_to_price_level.get(id)) {
+                (BuyOrSellAtPriceLevel::Buy(price), Some(BuyOrSellAtPriceLevel::Buy(indexed)))
+                | (
+                    BuyOrSellAtPriceLevel::Sell(price),
+                    Some(BuyOrSellAtPriceLevel::Sell(indexed)),
+                ) => price == indexed,
+                _ => false,
+            };
+            if !indexed {
+                return Err(InvalidOrderBook::Unindexed(*id));
+            }
+            if !seen.insert(id) {
+                return Err(InvalidOrderBook::Duplicate(*id));
+            }
+            if quantity.is_zero() {
+                return Err(InvalidOrderBook::NoQuantity(*id));
+            }
+        }
+
+        if let (Some(best_bid), Some(best_ask)) =
+            (self.buys.keys().next_back(), self.sells.keys().next())
+        {
+            if best_bid >= best_ask {
+                return Err(InvalidOrderBook::Crossed);
+            }
+        }
+        if let Some(notional) = &self.notional {
+            if !notional.0.counts(&mut resting(&self.buys, &self.sells)) {
+                return Err(InvalidOrderBook::NotionalDrift);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What happened to an incoming order, regardless of its side.
+#[derive(Debug)]
+enum MatchOutcome<QuantityT, PriceT, OrderIdT> {
+    /// The order didn't cross the book, so rests there.
+    EnteredOrderBook { id: OrderIdT },
+    TakerFullyExecuted {
+        maker: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        makers_remaining: QuantityT,
+    },
+    MutualFullExecution {
+        maker: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+    },
+    MakerFullyExecuted {
+        maker: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        takers_remaining: QuantityT,
+    },
+    /// The order exhausted every level it crossed, and its remainder rests as `id`.
+    PartiallyExecutedThenEntered {
+        id: OrderIdT,
+        maker: OrderIdT,
+        spread: Option<Positive<PriceT>>,
+        takers_remaining: QuantityT,
+    },
+    /// The order neither traded nor rests.
+    Rejected,
+}
+
+impl<QuantityT, PriceT, OrderIdT> MatchOutcome<QuantityT, PriceT, OrderIdT> {
+    fn into_buy(self) -> BuyEntryOrExecution<QuantityT, PriceT, OrderIdT> {
+        match self {
+            MatchOutcome::EnteredOrderBook { id } => BuyEntryOrExecution::EnteredOrderBook { id },
+            MatchOutcome::TakerFullyExecuted {
+                maker,
+                spread,
+                makers_remaining,
+            } => BuyEntryOrExecution::BuyerFullyExecuted {
+                seller: maker,
+                spread,
+                sellers_remaining: makers_remaining,
+            },
+            MatchOutcome::MutualFullExecution { maker, spread } => {
+                BuyEntryOrExecution::MutualFullExecution {
+                    seller: maker,
+                    spread,
+                }
+            }
+            MatchOutcome::MakerFullyExecuted {
+                maker,
+                spread,
+                takers_remaining,
+            } => BuyEntryOrExecution::SellerFullyExecuted {
+                seller: maker,
+                spread,
+                buyers_remaining: takers_remaining,
+            },
+            MatchOutcome::PartiallyExecutedThenEntered {
+                id,
+                maker,
+                spread,
+                takers_remaining,
+            } => BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                seller: maker,
+                spread,
+                buyers_remaining: takers_remaining,
+            },
+            MatchOutcome::Rejected => BuyEntryOrExecution::Rejected,
+        }
+    }
+
+    fn into_sell(self) -> SellEntryOrExecution<QuantityT, PriceT, OrderIdT> {
+        match self {
+            MatchOutcome::EnteredOrderBook { id } => SellEntryOrExecution::EnteredOrderBook { id },
+            MatchOutcome::TakerFullyExecuted {
+                maker,
+                spread,
+                makers_remaining,
+            } => SellEntryOrExecution::SellerFullyExecuted {
+                buyer: maker,
+                spread,
+                buyers_remaining: makers_remaining,
+            },
+            MatchOutcome::MutualFullExecution { maker, spread } => {
+                SellEntryOrExecution::MutualFullExecution {
+                    buyer: maker,
+                    spread,
+                }
+            }
+            MatchOutcome::MakerFullyExecuted {
+                maker,
+                spread,
+                takers_remaining,
+            } => SellEntryOrExecution::BuyerFullyExecuted {
+                buyer: maker,
+                spread,
+                sellers_remaining: takers_remaining,
+            },
+            MatchOutcome::PartiallyExecutedThenEntered {
+                id,
+                maker,
+                spread,
+                takers_remaining,
+            } => SellEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                buyer: maker,
+                spread,
+                sellers_remaining: takers_remaining,
+            },
+            MatchOutcome::Rejected => SellEntryOrExecution::Rejected,
+        }
+    }
+}
+
+impl<QuantityT, PriceT, OrderIdT> MatchOutcome<QuantityT, PriceT, OrderIdT>
+where
+    QuantityT: num::Zero + Clone,
+    PriceT: Clone,
+    OrderIdT: Clone,
+{
+    /// The outcome of a walk through the makers which ended with `fill`.
+    fn ended_with(fill: &FillReport<QuantityT, PriceT, OrderIdT>) -> Self {
+        let maker = fill.maker.clone();
+        let spread = fill.price_improvement.clone();
+        match (
+            fill.taker_remaining.is_zero(),
+            fill.maker_remaining.is_zero(),
+        ) {
+            (true, true) => MatchOutcome::MutualFullExecution { maker, spread },
+            (true, false) => MatchOutcome::TakerFullyExecuted {
+                maker,
+                spread,
+                makers_remaining: fill.maker_remaining.clone(),
+            },
+            (false, _) => MatchOutcome::MakerFullyExecuted {
+                maker,
+                spread,
+                takers_remaining: fill.taker_remaining.clone(),
+            },
+        }
+    }
+}
+
+/// How an incoming order may trade, besides its price.
+struct TakerLimits<QuantityT> {
+    /// The least it may trade at once, whether incoming or once it rests.
+    min_quantity: Option<QuantityT>,
+    /// The most makers it may trade against, after which it stops walking the book.
+    max_makers: Option<NonZeroUsize>,
+}
+
+impl<QuantityT> Default for TakerLimits<QuantityT> {
+    fn default() -> Self {
+        Self {
+            min_quantity: None,
+            max_makers: None,
+        }
+    }
+}
+
+type Matched<QuantityT, PriceT, OrderIdT> = (
+    MatchOutcome<QuantityT, PriceT, OrderIdT>,
+    Vec<FillReport<QuantityT, PriceT, OrderIdT>>,
+);
+
+/// Match an incoming `taker` order against the best level of `levels`, which holds the opposite side,
+/// regardless of any minimum quantities.
+///
+/// Never returns [`MatchOutcome::EnteredOrderBook`]: instead returns [`None`] if the order doesn't cross the book,
+/// or [`Err`] if `condition` aborted it, in which case `levels` is untouched.
+/// Fully executed makers are removed from `levels`, but not from any index.
+fn match_against<QuantityT, PriceT, OrderIdT, AbortReasonT>(
+    levels: &mut Levels<QuantityT, PriceT, OrderIdT>,
+    taker: Side,
+    quantity: QuantityT,
+    unit_price: &PriceT,
+    condition: impl FnOnce(&OrderIdT) -> ControlFlow<AbortReasonT, ()>,
+) -> Result<Option<Matched<QuantityT, PriceT, OrderIdT>>, AbortReasonT>
+where
+    QuantityT: Unsigned + Clone + Ord,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+    OrderIdT: Clone + Eq + Hash,
+{
+    let best_level = match taker {
+        Side::Buy => levels.first_key_value(),
+        Side::Sell => levels.last_key_value(),
+    };
+    let Some((maker_price, level)) = best_level else {
+        return Ok(None);
+    };
+    let crosses = match taker {
+        Side::Buy => maker_price <= unit_price,
+        Side::Sell => maker_price >= unit_price,
+    };
+    if !crosses {
+        return Ok(None);
+    }
+    if let ControlFlow::Break(reason) = condition(&level.front().0) {
+        return Err(reason);
+    }
+    let prices = (
+        ops::Bound::Included(maker_price.clone()),
+        ops::Bound::Included(maker_price.clone()),
+    );
+    let mut fills = Vec::new();
+    let outcome = walk_levels(
+        levels,
+        taker,
+        quantity,
+        unit_price,
+        prices,
+        &mut HashMap::new(),
+        |fill| {
+            fills.push(fill);
+            ControlFlow::Continue(())
+        },
+    );
+    Ok(outcome.map(|outcome| (outcome, fills)))
+}
+
+/// Trade an incoming `taker` order at `unit_price` against the levels of `levels` priced within `prices`,
+/// which hold the opposite side, best first and each in queue order, until the taker is filled.
+///
+/// A maker is passed over, keeping its place, if its fill would be less than its minimum in `min_quantities`,
+/// unless that fill is all it has left.
+/// Fully executed makers are removed from `levels` and `min_quantities`, but not from any index.
+///
+/// `on_fill` is called with each fill as it happens, and the walk stops there if it returns [`ControlFlow::Break`].
+///
+/// Returns the outcome as of the last fill, if anything traded,
+/// so only returns [`MatchOutcome::MakerFullyExecuted`] once every maker in `prices` which could trade has,
+/// or `on_fill` stopped the walk.
+fn walk_levels<QuantityT, PriceT, OrderIdT>(
+    levels: &mut Levels<QuantityT, PriceT, OrderIdT>,
+    taker: Side,
+    quantity: QuantityT,
+    unit_price: &PriceT,
+    prices: (ops::Bound<PriceT>, ops::Bound<PriceT>),
+    min_quantities: &mut HashMap<OrderIdT, QuantityT>,
+    mut on_fill: impl FnMut(FillReport<QuantityT, PriceT, OrderIdT>) -> ControlFlow<()>,
+) -> Option<MatchOutcome<QuantityT, PriceT, OrderIdT>>
+where
+    QuantityT: Unsigned + Clone + Ord,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+    OrderIdT: Clone + Eq + Hash,
+{
+    let crossing: Box<dyn Iterator<Item = (&PriceT, &mut _)>> = match taker {
+        Side::Buy => Box::new(levels.range_mut(prices)),
+        Side::Sell => Box::new(levels.range_mut(prices).rev()),
+    };
+    let mut takers_remaining = quantity;
+    let mut outcome = None;
+    // makers are emptied in place, and removed once the walk is over
+    let mut emptied = Vec::new();
+    'walk: for (maker_price, level) in crossing {
+        for (maker, makers_quantity) in level.iter_mut() {
+            if takers_remaining.is_zero() {
+                break 'walk;
+            }
+            let Some(fill) = fill_against(
+                maker.clone(),
+                makers_quantity,
+                maker_price,
+                &takers_remaining,
+                unit_price,
+                min_quantities.get(maker),
+            ) else {
+                continue;
+            };
+            takers_remaining = fill.taker_remaining.clone();
+            *makers_quantity = fill.maker_remaining.clone();
+            if makers_quantity.is_zero() {
+                min_quantities.remove(maker);
+                if emptied.last() != Some(maker_price) {
+                    emptied.push(maker_price.clone())
+                }
+            }
+            outcome = Some(MatchOutcome::ended_with(&fill));
+            if on_fill(fill).is_break() {
+                break 'walk;
+            }
+        }
+    }
+    for maker_price in emptied {
+        let level = levels.remove(&maker_price).expect("level was just walked");
+        if let Some(level) = level.retain(|(_, quantity)| !quantity.is_zero()) {
+            levels.insert_uncontended(maker_price, level)
+        }
+    }
+    outcome
+}
+
+/// Like [`walk_levels`], through every level which crosses `unit_price`, except those which `max_adverse_spread`
+/// protects from the taker, as described by [`PriceLevelBTreeOrderBook::with_max_adverse_spread`].
+fn walk_unprotected_levels<QuantityT, PriceT, OrderIdT>(
+    levels: &mut Levels<QuantityT, PriceT, OrderIdT>,
+    taker: Side,
+    quantity: QuantityT,
+    unit_price: &PriceT,
+    max_adverse_spread: Option<&PriceT>,
+    min_quantities: &mut HashMap<OrderIdT, QuantityT>,
+    on_fill: impl FnMut(FillReport<QuantityT, PriceT, OrderIdT>) -> ControlFlow<()>,
+) -> Option<MatchOutcome<QuantityT, PriceT, OrderIdT>>
+where
+    QuantityT: Unsigned + Clone + Ord,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+    OrderIdT: Clone + Eq + Hash,
+{
+    let prices = unprotected_prices(levels, taker, unit_price, max_adverse_spread)?;
+    walk_levels(
+        levels,
+        taker,
+        quantity,
+        unit_price,
+        prices,
+        min_quantities,
+        on_fill,
+    )
+}
+
+/// The prices of the levels in `levels` which cross `unit_price` and which `max_adverse_spread` doesn't protect
+/// from the taker, or [`None`] if there are none.
+fn unprotected_prices<PriceT, LevelT>(
+    levels: &BTreeMap<PriceT, LevelT>,
+    taker: Side,
+    unit_price: &PriceT,
+    max_adverse_spread: Option<&PriceT>,
+) -> Option<(ops::Bound<PriceT>, ops::Bound<PriceT>)>
+where
+    PriceT: Clone + Ord + ops::Sub<Output = PriceT>,
+{
+    let limit = ops::Bound::Included(unit_price.clone());
+    if max_adverse_spread.is_none() {
+        return Some(match taker {
+            Side::Buy => (ops::Bound::Unbounded, limit),
+            Side::Sell => (limit, ops::Bound::Unbounded),
+        });
+    }
+    // the further a level crosses, the more protected it is,
+    // so only the crossing levels before the first unprotected one are looked at
+    let unprotected =
+        |maker_price: &&PriceT| !is_protected(taker, *maker_price, unit_price, max_adverse_spread);
+    let first = match taker {
+        Side::Buy => levels
+            .range(..=unit_price)
+            .map(|(maker_price, _)| maker_price)
+            .find(unprotected)?,
+        Side::Sell => levels
+            .range(unit_price..)
+            .rev()
+            .map(|(maker_price, _)| maker_price)
+            .find(unprotected)?,
+    };
+    let first = ops::Bound::Included(first.clone());
+    Some(match taker {
+        Side::Buy => (first, limit),
+        Side::Sell => (limit, first),
+    })
+}
+
+/// What [`walk_unprotected_levels`] would trade, without changing `levels`.
+///
+/// `on_fill` sees each fill in the order it would happen, and may abort the walk.
+/// Returns the outcome [`walk_unprotected_levels`] would, if anything would trade,
+/// and the best price which would be left in `levels`.
+#[allow(clippy::type_complexity)]
+fn dry_walk<'a, QuantityT, PriceT, OrderIdT, AbortReasonT>(
+    levels: &'a Levels<QuantityT, PriceT, OrderIdT>,
+    taker: Side,
+    quantity: QuantityT,
+    unit_price: &PriceT,
+    max_adverse_spread: Option<&PriceT>,
+    min_quantities: &HashMap<OrderIdT, QuantityT>,
+    mut on_fill: impl FnMut(&FillReport<QuantityT, PriceT, OrderIdT>) -> ControlFlow<AbortReasonT, ()>,
+) -> Result<
+    (
+        Option<MatchOutcome<QuantityT, PriceT, OrderIdT>>,
+        Option<&'a PriceT>,
+    ),
+    AbortReasonT,
+>
+where
+    QuantityT: Unsigned + Clone + Ord,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+    OrderIdT: Clone + Eq + Hash,
+{
+    let best = best_maker_price(levels, taker);
+    let Some(prices) = unprotected_prices(levels, taker, unit_price, max_adverse_spread) else {
+        return Ok((None, best));
+    };
+    // protected levels are better than any other, and are left alone
+    let mut best_left = best
+        .filter(|maker_price| is_protected(taker, *maker_price, unit_price, max_adverse_spread));
+    let crossing: Box<dyn Iterator<Item = (&PriceT, &_)>> = match taker {
+        Side::Buy => Box::new(levels.range(prices)),
+        Side::Sell => Box::new(levels.range(prices).rev()),
+    };
+    let mut takers_remaining = quantity;
+    let mut outcome = None;
+    'walk: for (maker_price, level) in crossing {
+        for (maker, makers_quantity) in level.iter() {
+            if takers_remaining.is_zero() {
+                best_left.get_or_insert(maker_price);
+                break 'walk;
+            }
+            let Some(fill) = fill_against(
+                maker.clone(),
+                makers_quantity,
+                maker_price,
+                &takers_remaining,
+                unit_price,
+                min_quantities.get(maker),
+            ) else {
+                best_left.get_or_insert(maker_price);
+                continue;
+            };
+            if let ControlFlow::Break(reason) = on_fill(&fill) {
+                return Err(reason);
+            }
+            if !fill.maker_remaining.is_zero() {
+                best_left.get_or_insert(maker_price);
+            }
+            takers_remaining = fill.taker_remaining.clone();
+            outcome = Some(MatchOutcome::ended_with(&fill));
+        }
+    }
+    // every crossing level would be emptied
+    let best_left = best_left.or_else(|| {
+        match taker {
+            Side::Buy => levels
+                .range((ops::Bound::Excluded(unit_price), ops::Bound::Unbounded))
+                .next(),
+            Side::Sell => levels.range(..unit_price).next_back(),
+        }
+        .map(|(maker_price, _)| maker_price)
+    });
+    Ok((outcome, best_left))
+}
+
+/// The fill a taker at `unit_price`, with `takers_remaining`, gets from `maker`, which has `makers_quantity` at `maker_price`,
+/// or [`None`] if that is less than the maker's `min_quantity`, unless it is all the maker has left.
+fn fill_against<QuantityT, PriceT, OrderIdT>(
+    maker: OrderIdT,
+    makers_quantity: &QuantityT,
+    maker_price: &PriceT,
+    takers_remaining: &QuantityT,
+    unit_price: &PriceT,
+    min_quantity: Option<&QuantityT>,
+) -> Option<FillReport<QuantityT, PriceT, OrderIdT>>
+where
+    QuantityT: Unsigned + Clone + Ord,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+{
+    let quantity = takers_remaining.min(makers_quantity).clone();
+    if min_quantity
+        .is_some_and(|min_quantity| quantity < *min_quantity && quantity < *makers_quantity)
+    {
+        return None;
+    }
+    // the prices cross, so this is the right way round
+    let price_improvement = match maker_price.cmp(unit_price) {
+        Ordering::Less => Some(Positive::new(unit_price.clone() - maker_price.clone()).unwrap()),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(Positive::new(maker_price.clone() - unit_price.clone()).unwrap()),
+    };
+    Some(FillReport {
+        maker,
+        unit_price: maker_price.clone(),
+        taker_remaining: takers_remaining.clone() - quantity.clone(),
+        maker_remaining: makers_quantity.clone() - quantity.clone(),
+        quantity,
+        price_improvement,
+    })
+}
+
+/// The best price of `levels`, which are on the other side to `taker`.
+fn best_maker_price<PriceT, LevelT>(
+    levels: &BTreeMap<PriceT, LevelT>,
+    taker: Side,
+) -> Option<&PriceT> {
+    match taker {
+        Side::Buy => levels.keys().next(),
+        Side::Sell => levels.keys().next_back(),
+    }
+}
+
+/// Whether a taker on `taker` at `unit_price` is priced more than `max_adverse_spread` through a maker at `maker_price`.
+fn is_protected<PriceT>(
+    taker: Side,
+    maker_price: &PriceT,
+    unit_price: &PriceT,
+    max_adverse_spread: Option<&PriceT>,
+) -> bool
+where
+    PriceT: Clone + Ord + ops::Sub<Output = PriceT>,
+{
+    let Some(max_adverse_spread) = max_adverse_spread else {
+        return false;
+    };
+    let spread = match taker {
+        Side::Buy if maker_price < unit_price => unit_price.clone() - maker_price.clone(),
+        Side::Sell if maker_price > unit_price => maker_price.clone() - unit_price.clone(),
+        _ => return false,
+    };
+    spread > *max_adverse_spread
+}
+
+impl<QuantityT, PriceT> PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug + Measurable,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero + Measurable,
+{
+    /// What [`FillReportingOrderBookApi::conditional_buy_reporting_fills`] would return, without changing the book.
+    ///
+    /// If the buy would enter the book, the returned id is never used.
+    pub fn dry_run_buy(
+        &self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> BuyWithFills<QuantityT, PriceT, uuid::Uuid> {
+        let (outcome, fills) = self.dry_run(Side::Buy, quantity.into_inner(), unit_price);
+        (outcome.into_buy(), fills)
+    }
+
+    /// What [`FillReportingOrderBookApi::conditional_sell_reporting_fills`] would return, without changing the book.
+    ///
+    /// If the sell would enter the book, the returned id is never used.
+    pub fn dry_run_sell(
+        &self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> SellWithFills<QuantityT, PriceT, uuid::Uuid> {
+        let (outcome, fills) = self.dry_run(Side::Sell, quantity.into_inner(), unit_price);
+        (outcome.into_sell(), fills)
+    }
+
+    /// Every resident order, with the [sequence number](Self::current_sequence) of the last change they reflect,
+    /// so that changes after that point may be applied to the snapshot.
+    pub fn snapshot_and_sequence(&self) -> (BookSnapshot<QuantityT, PriceT, uuid::Uuid>, u64) {
+        (self.snapshot(), self.sequence)
+    }
+
+    /// Like [`FillReportingOrderBookApi::conditional_buy_reporting_fills`],
+    /// but `condition` may also inspect the book before any trade occurs.
+    pub fn conditional_buy_inspecting_book<BuyAbortReasonT>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(
+            &Self,
+            ConditionalBuyArgs<'_, uuid::Uuid>,
+        ) -> ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyWithFills<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
+        self.match_or_rest(
+            Side::Buy,
+            quantity.into_inner(),
+            unit_price,
+            TakerLimits::default(),
+            |book, seller_id| condition(book, ConditionalBuyArgs { seller_id }),
+        )
+        .map(|(outcome, fills)| (outcome.into_buy(), fills))
+    }
+
+    /// Like [`FillReportingOrderBookApi::conditional_sell_reporting_fills`],
+    /// but `condition` may also inspect the book before any trade occurs.
+    pub fn conditional_sell_inspecting_book<SellAbortReasonT>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(
+            &Self,
+            ConditionalSellArgs<'_, uuid::Uuid>,
+        ) -> ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellWithFills<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
+        self.match_or_rest(
+            Side::Sell,
+            quantity.into_inner(),
+            unit_price,
+            TakerLimits::default(),
+            |book, buyer_id| condition(book, ConditionalSellArgs { buyer_id }),
+        )
+        .map(|(outcome, fills)| (outcome.into_sell(), fills))
+    }
+
+    /// A buy which, if it trades, must trade at least `min_quantity`.
+    ///
+    /// If less would execute, the buy is rejected, and the book is left exactly as it was before the call.
+    /// A buy which doesn't cross the book rests as usual, and keeps `min_quantity`:
+    /// an incoming sell passes over it, leaving it in its place, unless it would trade at least `min_quantity`
+    /// or all that is left of the buy.
+    pub fn buy_with_min_quantity(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        min_quantity: QuantityT,
+    ) -> Result<BuyWithFills<QuantityT, PriceT, uuid::Uuid>, MinimumQuantityNotMet> {
+        let (outcome, fills) = self.match_or_rest_min_quantity(
+            Side::Buy,
+            quantity.into_inner(),
+            unit_price,
+            min_quantity,
+        )?;
+        Ok((outcome.into_buy(), fills))
+    }
+
+    /// A sell which, if it trades, must trade at least `min_quantity`.
+    ///
+    /// If less would execute, the sell is rejected, and the book is left exactly as it was before the call.
+    /// A sell which doesn't cross the book rests as usual, and keeps `min_quantity`:
+    /// an incoming buy passes over it, leaving it in its place, unless it would trade at least `min_quantity`
+    /// or all that is left of the sell.
+    pub fn sell_with_min_quantity(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        min_quantity: QuantityT,
+    ) -> Result<SellWithFills<QuantityT, PriceT, uuid::Uuid>, MinimumQuantityNotMet> {
+        let (outcome, fills) = self.match_or_rest_min_quantity(
+            Side::Sell,
+            quantity.into_inner(),
+            unit_price,
+            min_quantity,
+        )?;
+        Ok((outcome.into_sell(), fills))
+    }
+
+    fn match_or_rest_min_quantity(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+        min_quantity: QuantityT,
+    ) -> Result<Matched<QuantityT, PriceT, uuid::Uuid>, MinimumQuantityNotMet> {
+        // nothing trades unless the book is open
+        if self.status == BookStatus::Open {
+            let limit = self.collared(taker, unit_price.clone());
+            let mut executed = QuantityT::zero();
+            let walked = dry_walk(
+                self.makers(taker),
+                taker,
+                quantity.clone(),
+                &limit,
+                self.max_adverse_spread.as_ref(),
+                &self.min_quantities,
+                |fill| {
+                    executed = executed.clone() + fill.quantity.clone();
+                    match executed >= min_quantity {
+                        true => ControlFlow::Break(()),
+                        false => ControlFlow::Continue(()),
+                    }
+                },
+            );
+            // the walk only runs its course if it trades too little
+            if let Ok((Some(_), _)) = walked {
+                return Err(MinimumQuantityNotMet);
+            }
+        }
+        let limits = TakerLimits {
+            min_quantity: Some(min_quantity),
+            ..TakerLimits::default()
+        };
+        match self.match_or_rest(taker, quantity, unit_price, limits, |_, _| {
+            ControlFlow::<Infallible>::Continue(())
+        }) {
+            Ok(matched) => Ok(matched),
+            Err(never) => match never {},
+        }
+    }
+
+    /// A buy which trades against at most `max_counterparties` makers.
+    ///
+    /// The book is walked until the cap is reached, and what's left is returned rather than rested,
+    /// unless it no longer crosses the book, in which case it rests as usual.
+    pub fn buy_with_max_counterparties(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        max_counterparties: NonZeroUsize,
+    ) -> (BuyWithFills<QuantityT, PriceT, uuid::Uuid>, QuantityT) {
+        let ((outcome, fills), unfilled) = self.match_or_rest_max_counterparties(
+            Side::Buy,
+            quantity.into_inner(),
+            unit_price,
+            max_counterparties,
+        );
+        ((outcome.into_buy(), fills), unfilled)
+    }
+
+    /// A sell which trades against at most `max_counterparties` makers.
+    ///
+    /// The book is walked until the cap is reached, and what's left is returned rather than rested,
+    /// unless it no longer crosses the book, in which case it rests as usual.
+    pub fn sell_with_max_counterparties(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        max_counterparties: NonZeroUsize,
+    ) -> (SellWithFills<QuantityT, PriceT, uuid::Uuid>, QuantityT) {
+        let ((outcome, fills), unfilled) = self.match_or_rest_max_counterparties(
+            Side::Sell,
+            quantity.into_inner(),
+            unit_price,
+            max_counterparties,
+        );
+        ((outcome.into_sell(), fills), unfilled)
+    }
+
+    fn match_or_rest_max_counterparties(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+        max_counterparties: NonZeroUsize,
+    ) -> (Matched<QuantityT, PriceT, uuid::Uuid>, QuantityT) {
+        let limits = TakerLimits {
+            max_makers: Some(max_counterparties),
+            ..TakerLimits::default()
+        };
+        let (outcome, fills) =
+            match self.match_or_rest(taker, quantity, unit_price, limits, |_, _| {
+                ControlFlow::<Infallible>::Continue(())
+            }) {
+                Ok(matched) => matched,
+                Err(never) => match never {},
+            };
+        // what rested is reported as `PartiallyExecutedThenEntered`
+        let unfilled = match &outcome {
+            MatchOutcome::MakerFullyExecuted {
+                takers_remaining, ..
+            } => takers_remaining.clone(),
+            _ => QuantityT::zero(),
+        };
+        ((outcome, fills), unfilled)
+    }
+
+    /// Like [`FillReportingOrderBookApi::conditional_buy_reporting_fills`],
+    /// but `condition` is called with each fill before any trade occurs, in the order they would occur.
+    ///
+    /// If it returns [`ControlFlow::Break`] for any fill, the buy is aborted,
+    /// and the book is left exactly as it was before the call.
+    pub fn conditional_buy_checking_fills<BuyAbortReasonT>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnMut(
+            &FillReport<QuantityT, PriceT, uuid::Uuid>,
+        ) -> ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyWithFills<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
+        let (outcome, fills) = self.match_or_rest_checking_fills(
+            Side::Buy,
+            quantity.into_inner(),
+            unit_price,
+            condition,
+        )?;
+        Ok((outcome.into_buy(), fills))
+    }
+
+    /// Like [`FillReportingOrderBookApi::conditional_sell_reporting_fills`],
+    /// but `condition` is called with each fill before any trade occurs, in the order they would occur.
+    ///
+    /// If it returns [`ControlFlow::Break`] for any fill, the sell is aborted,
+    /// and the book is left exactly as it was before the call.
+    pub fn conditional_sell_checking_fills<SellAbortReasonT>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnMut(
+            &FillReport<QuantityT, PriceT, uuid::Uuid>,
+        ) -> ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellWithFills<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
+        let (outcome, fills) = self.match_or_rest_checking_fills(
+            Side::Sell,
+            quantity.into_inner(),
+            unit_price,
+            condition,
+        )?;
+        Ok((outcome.into_sell(), fills))
+    }
+
+    /// Check each fill of a walk which only reads the makers, and only then trade.
+    fn match_or_rest_checking_fills<AbortReasonT>(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+        mut condition: impl FnMut(
+            &FillReport<QuantityT, PriceT, uuid::Uuid>,
+        ) -> ControlFlow<AbortReasonT, ()>,
+    ) -> Result<Matched<QuantityT, PriceT, uuid::Uuid>, AbortReasonT> {
+        // nothing trades unless the book is open
+        if self.status == BookStatus::Open {
+            let limit = self.collared(taker, unit_price.clone());
+            dry_walk(
+                self.makers(taker),
+                taker,
+                quantity.clone(),
+                &limit,
+                self.max_adverse_spread.as_ref(),
+                &self.min_quantities,
+                &mut condition,
+            )?;
+        }
+        match self.match_or_rest(
+            taker,
+            quantity,
+            unit_price,
+            TakerLimits::default(),
+            |_, _| ControlFlow::<Infallible>::Continue(()),
+        ) {
+            Ok(matched) => Ok(matched),
+            Err(never) => match never {},
+        }
+    }
+
+    /// What [`Self::match_or_rest`] would do, from a walk which only reads the other side of the book.
+    fn dry_run(
+        &self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+    ) -> Matched<QuantityT, PriceT, uuid::Uuid> {
+        match self.status {
+            BookStatus::Open => {}
+            BookStatus::Halted => return (MatchOutcome::Rejected, vec![]),
+            BookStatus::AuctionOnly => {
+                let id = uuid::Uuid::new_v4();
+                return (MatchOutcome::EnteredOrderBook { id }, vec![]);
+            }
+        }
+        let limit = self.collared(taker, unit_price.clone());
+        let mut fills = vec![];
+        let walked = dry_walk(
+            self.makers(taker),
+            taker,
+            quantity,
+            &limit,
+            self.max_adverse_spread.as_ref(),
+            &self.min_quantities,
+            |fill| {
+                fills.push(fill.clone());
+                ControlFlow::<Infallible>::Continue(())
+            },
+        );
+        let (matched, best_left) = walked.unwrap_or_else(|never| match never {});
+        let rests = self
+            .resting_price(taker, best_left, unit_price, limit)
+            .is_some();
+        match (matched, rests) {
+            (
+                Some(MatchOutcome::MakerFullyExecuted {
+                    maker,
+                    spread,
+                    takers_remaining,
+                }),
+                true,
+            ) => (
+                MatchOutcome::PartiallyExecutedThenEntered {
+                    id: uuid::Uuid::new_v4(),
+                    maker,
+                    spread,
+                    takers_remaining,
+                },
+                fills,
+            ),
+            (Some(outcome), _) => (outcome, fills),
+            (None, true) => (
+                MatchOutcome::EnteredOrderBook {
+                    id: uuid::Uuid::new_v4(),
+                },
+                vec![],
+            ),
+            (None, false) => (MatchOutcome::Rejected, vec![]),
+        }
+    }
+
+    /// Match an incoming order against the other side of the book, through as many levels as it crosses,
+    /// and rest whatever is left.
+    fn match_or_rest<AbortReasonT>(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+        limits: TakerLimits<QuantityT>,
+        condition: impl FnOnce(&Self, &uuid::Uuid) -> ControlFlow<AbortReasonT, ()>,
+    ) -> Result<Matched<QuantityT, PriceT, uuid::Uuid>, AbortReasonT> {
+        self.match_or_rest_streaming(taker, quantity, unit_price, limits, condition, |_| {})
+    }
+
+    /// Like [`Self::match_or_rest`], calling `on_trade` with each trade as the walk makes it.
+    fn match_or_rest_streaming<AbortReasonT>(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+        limits: TakerLimits<QuantityT>,
+        condition: impl FnOnce(&Self, &uuid::Uuid) -> ControlFlow<AbortReasonT, ()>,
+        mut on_trade: impl FnMut(Trade<QuantityT, PriceT, uuid::Uuid>),
+    ) -> Result<Matched<QuantityT, PriceT, uuid::Uuid>, AbortReasonT> {
+        execution_metrics::order_submitted(taker, &quantity);
+        match self.status {
+            BookStatus::Open => {}
+            BookStatus::Halted => return Ok((MatchOutcome::Rejected, vec![])),
+            BookStatus::AuctionOnly => {
+                self.next_sequence();
+                let id = self.rest_limited(taker, quantity, unit_price, limits);
+                return Ok((MatchOutcome::EnteredOrderBook { id }, vec![]));
+            }
+        }
+        let limit = self.collared(taker, unit_price.clone());
+        // copy the counterparty out first, so that `condition` is free to inspect the book
+        if let Some(maker) = self.best_maker(taker, &limit) {
+            if let ControlFlow::Break(reason) = condition(self, &maker) {
+                return Err(reason);
+            }
+        }
+        let mut fills = vec![];
+        let max_adverse_spread = self.max_adverse_spread.clone();
+        // handed out by `next_sequence` once the walk is over, if anything traded
+        let sequence = self.sequence + 1;
+        let matched = self
+            .walk_makers(
+                taker,
+                quantity.clone(),
+                &limit,
+                max_adverse_spread,
+                |fill| {
+                    on_trade(Trade {
+                        sequence,
+                        taker_side: taker,
+                        maker: fill.maker,
+                        unit_price: fill.unit_price.clone(),
+                        quantity: fill.quantity.clone(),
+                    });
+                    fills.push(fill);
+                    match limits.max_makers {
+                        Some(max_makers) if fills.len() == max_makers.get() => {
+                            ControlFlow::Break(())
+                        }
+                        _ => ControlFlow::Continue(()),
+                    }
+                },
+            )
+            .map(|outcome| (outcome, fills));
+        let best_maker_price = best_maker_price(self.makers(taker), taker);
+        let resting_price = self.resting_price(taker, best_maker_price, unit_price, limit);
+        match matched {
+            Some((outcome, fills)) => {
+                let sequence = self.next_sequence();
+                self.capture_spread(&fills);
+                self.count_level_hits(&fills);
+                self.retire_filled_makers(taker, &fills);
+                self.record_trades(sequence, taker, &fills);
+                execution_metrics::trades_executed(taker, &fills);
+                let outcome = match (outcome, resting_price) {
+                    (
+                        MatchOutcome::MakerFullyExecuted {
+                            maker,
+                            spread,
+                            takers_remaining,
+                        },
+                        Some(resting_price),
+                    ) => {
+                        let id = self.rest_limited(
+                            taker,
+                            takers_remaining.clone(),
+                            resting_price,
+                            limits,
+                        );
+                        self.record_counterparties(id, &fills);
+                        MatchOutcome::PartiallyExecutedThenEntered {
+                            id,
+                            maker,
+                            spread,
+                            takers_remaining,
+                        }
+                    }
+                    (outcome, _) => outcome,
+                };
+                Ok((outcome, fills))
+            }
+            None => match resting_price {
+                Some(resting_price) => {
+                    self.next_sequence();
+                    let id = self.rest_limited(taker, quantity, resting_price, limits);
+                    Ok((MatchOutcome::EnteredOrderBook { id }, vec![]))
+                }
+                None => Ok((MatchOutcome::Rejected, vec![])),
+            },
+        }
+    }
+
+    /// `unit_price`, or the edge of the [collar](Self::with_price_collar) if it is priced beyond it.
+    fn collared(&self, taker: Side, unit_price: PriceT) -> PriceT {
+        let (Some(collar), Some(reference)) = (&self.price_collar, &self.last_trade_price) else {
+            return unit_price;
+        };
+        // the edge of the band lies between the reference and `unit_price`, so can't overflow
+        match taker {
+            Side::Buy
+                if unit_price > *reference
+                    && unit_price.clone() - reference.clone() > collar.band =>
+            {
+                reference.clone() + collar.band.clone()
+            }
+            Side::Sell
+                if unit_price < *reference
+                    && reference.clone() - unit_price.clone() > collar.band =>
+            {
+                reference.clone() - collar.band.clone()
+            }
+            _ => unit_price,
+        }
+    }
+
+    /// Where what's left of an incoming order at `unit_price`, having traded up to `limit`, may rest,
+    /// given the best price left on the other side.
+    ///
+    /// It mustn't rest crossing the levels it skipped, whether [protected](Self::with_max_adverse_spread)
+    /// or beyond the [collar](Self::with_price_collar).
+    fn resting_price(
+        &self,
+        taker: Side,
+        best_maker_price: Option<&PriceT>,
+        unit_price: PriceT,
+        limit: PriceT,
+    ) -> Option<PriceT> {
+        let crosses = |price: &PriceT| match (taker, best_maker_price) {
+            (_, None) => false,
+            (Side::Buy, Some(best)) => best <= price,
+            (Side::Sell, Some(best)) => best >= price,
+        };
+        match (crosses(&unit_price), crosses(&limit)) {
+            (false, _) => Some(unit_price),
+            // protected levels cross even the collared price
+            (true, true) => None,
+            (true, false) => match self.price_collar.as_ref()?.remainder {
+                CollarRemainder::Cancel => None,
+                CollarRemainder::Rest => Some(limit),
+            },
+        }
+    }
+
+    /// Remember the makers of `fills` for [`Self::fills_of`] the taker, `id`.
+    fn record_counterparties(
+        &mut self,
+        id: uuid::Uuid,
+        fills: &[FillReport<QuantityT, PriceT, uuid::Uuid>],
+    ) {
+        self.counterparties
+            .entry(id)
+            .or_default()
+            .extend(fills.iter().map(|fill| (fill.maker, fill.quantity.clone())));
+    }
+
+    fn count_level_hits(&mut self, fills: &[FillReport<QuantityT, PriceT, uuid::Uuid>]) {
+        for fill in fills {
+            *self.level_hits.entry(fill.unit_price.clone()).or_default() += 1;
+        }
+    }
+
+    /// Add the price improvement of each of `fills` to the session total.
+    fn capture_spread(&mut self, fills: &[FillReport<QuantityT, PriceT, uuid::Uuid>]) {
+        for spread in fills
+            .iter()
+            .filter_map(|fill| fill.price_improvement.clone())
+        {
+            let spread = spread.into_inner();
+            self.session_spread_captured = Some(match self.session_spread_captured.take() {
+                Some(total) => total + spread,
+                None => spread,
+            });
+        }
+    }
+
+    /// The first maker an incoming order on `taker` would trade with, if it crosses the book,
+    /// skipping those protected by [`Self::with_max_adverse_spread`].
+    fn best_maker(&self, taker: Side, unit_price: &PriceT) -> Option<uuid::Uuid> {
+        let max_adverse_spread = self.max_adverse_spread.as_ref();
+        let unprotected = |(maker_price, _): &(&PriceT, _)| {
+            !is_protected(taker, *maker_price, unit_price, max_adverse_spread)
+        };
+        let makers = self.makers(taker);
+        let (_, level) = match taker {
+            Side::Buy => makers.range(..=unit_price).find(unprotected),
+            Side::Sell => makers.range(unit_price..).rev().find(unprotected),
+        }?;
+        Some(level.front().0)
+    }
+
+    /// Set aside `quantity` of a resting order, so that it isn't matched, until it is [released](Self::release)
+    /// or [committed](Self::commit).
+    ///
+    /// The order keeps its place in the queue, and must keep some quantity free.
+    /// Cancelling the order drops its holds, and reports the held quantity as cancelled too.
+    /// Filling the free quantity doesn't, so the holds can still be committed.
+    pub fn hold(
+        &mut self,
+        id: uuid::Uuid,
+        quantity: Positive<QuantityT>,
+    ) -> Result<HoldToken, HoldError> {
+        let price_level = self
+            .ids_to_price_level
+            .get(&id)
+            .ok_or(HoldError::NoSuchOrder)?
+            .clone();
+        let (side, levels, unit_price) = match &price_level {
+            BuyOrSellAtPriceLevel::Buy(price) => (Side::Buy, &mut self.buys, price),
+            BuyOrSellAtPriceLevel::Sell(price) => (Side::Sell, &mut self.sells, price),
+        };
+        let resting = levels
+            .get_mut(unit_price)
+            .and_then(|level| level.iter_mut().find(|(it_id, _)| it_id == &id));
+        let Some((_, free)) = resting else {
+            self.stale_index(&id, &price_level);
+            return Err(HoldError::NoSuchOrder);
+        };
+        let quantity = quantity.into_inner();
+        if quantity >= *free {
+            return Err(HoldError::InsufficientQuantity);
+        }
+        *free = free.clone() - quantity.clone();
+        self.notional_left(side, unit_price, &quantity);
+        let token = HoldToken(self.next_hold);
+        self.next_hold += 1;
+        self.holds.insert(
+            token,
+            Hold {
+                id,
+                side,
+                unit_price: unit_price.clone(),
+                quantity,
+            },
+        );
+        self.next_sequence();
+        Ok(token)
+    }
+
+    /// Return held quantity to its order, which must still be resting.
+    ///
+    /// If the order has since been filled, the hold is kept, and can only be [committed](Self::commit).
+    pub fn release(&mut self, token: HoldToken) -> Result<(), ReleaseError> {
+        let hold = self.holds.get(&token).ok_or(ReleaseError::NoSuchHold)?;
+        let levels = match hold.side {
+            Side::Buy => &mut self.buys,
+            Side::Sell => &mut self.sells,
+        };
+        let resting = match self.ids_to_price_level.contains_key(&hold.id) {
+            true => levels
+                .get_mut(&hold.unit_price)
+                .and_then(|level| level.iter_mut().find(|(it_id, _)| *it_id == hold.id)),
+            false => None,
+        };
+        let Some((_, free)) = resting else {
+            return Err(ReleaseError::NoSuchOrder);
+        };
+        *free = free.clone() + hold.quantity.clone();
+        let hold = self.holds.remove(&token).expect("hold was just found");
+        self.notional_entered(hold.side, &hold.unit_price, &hold.quantity);
+        self.next_sequence();
+        Ok(())
+    }
+
+    /// Remove held quantity from the book for good, returning it.
+    ///
+    /// This is reported as a [removal](Self::recent_removals) of the held quantity.
+    pub fn commit(&mut self, token: HoldToken) -> Result<QuantityT, NoSuchHold> {
+        let Hold {
+            id,
+            side,
+            unit_price,
+            quantity,
+        } = self.holds.remove(&token).ok_or(NoSuchHold)?;
+        self.next_sequence();
+        self.remove_order(OrderRemoved {
+            id,
+            reason: RemovalReason::Committed,
+            side,
+            price: unit_price,
+            quantity: quantity.clone(),
+        });
+        Ok(quantity)
+    }
+
+    /// Trade away any crossing orders, such as those rested without matching, until the book is uncrossed.
+    ///
+    /// The best bid is repeatedly matched against the asks as if it were incoming,
+    /// so each trade executes at the ask's resting price.
+    /// A partially executed bid keeps its place at the front of its level.
+    /// Minimum quantities are ignored, so that the book is always uncrossed.
+    pub fn resolve_crosses(&mut self) -> Vec<Trade<QuantityT, PriceT, uuid::Uuid>> {
+        let trades = self.uncross(Side::Buy, self.sequence + 1);
+        if !trades.is_empty() {
+            self.next_sequence();
+        }
+        trades
+    }
+
+    /// Reopen the book after [`Self::halt`] or [`Self::halt_for_auction`],
+    /// then [resolve](Self::resolve_crosses) any crosses left by orders rested in the meantime.
+    pub fn resume(&mut self) -> Vec<Trade<QuantityT, PriceT, uuid::Uuid>> {
+        self.status = BookStatus::Open;
+        self.resolve_crosses()
+    }
+
+    /// Move every order on `side` by `delta` in `direction`, keeping time priority within each level.
+    ///
+    /// If the book is [open](BookStatus::Open), orders which now cross the book are then matched as if incoming,
+    /// best first, so trades execute at the other side's resting prices,
+    /// within the [collar](Self::with_price_collar) and skipping [protected](Self::with_max_adverse_spread) levels.
+    /// Otherwise, they are left crossing the book until it [resumes](Self::resume).
+    ///
+    /// Returns [`PriceOutOfRange`], leaving the book untouched, if any price on `side` can't be moved that far.
+    pub fn shift_prices(
+        &mut self,
+        side: Side,
+        direction: ShiftDirection,
+        delta: PriceT,
+    ) -> Result<Vec<Trade<QuantityT, PriceT, uuid::Uuid>>, PriceOutOfRange>
+    where
+        PriceT: num::CheckedAdd + num::CheckedSub,
+    {
+        let shift = |price: &PriceT| match direction {
+            ShiftDirection::Up => price.checked_add(&delta),
+            ShiftDirection::Down => price.checked_sub(&delta),
+        };
+        // shifting preserves the order of prices, so of the levels only the extremes need checking,
+        // but a hold outlives its order if that is filled, so may be anywhere
+        let levels = self.levels(side);
+        let held = self.holds.values().filter(|hold| hold.side == side);
+        if [levels.first_key_value(), levels.last_key_value()]
+            .into_iter()
+            .flatten()
+            .map(|(price, _)| price)
+            .chain(held.map(|hold| &hold.unit_price))
+            .any(|price| shift(price).is_none())
+        {
+            return Err(PriceOutOfRange);
+        }
+        let shift = |price: &PriceT| shift(price).expect("price was checked");
+
+        // the index is updated while the levels are borrowed, so this can't go through `levels_mut`
+        let levels = match side {
+            Side::Buy => &mut self.buys,
+            Side::Sell => &mut self.sells,
+        };
+        *levels = std::mem::take(levels)
+            .into_iter()
+            .map(|(price, level)| (shift(&price), level))
+            .collect();
+        for (price, level) in levels.iter() {
+            for (id, _) in level.iter() {
+                let price_level = match side {
+                    Side::Buy => BuyOrSellAtPriceLevel::Buy(price.clone()),
+                    Side::Sell => BuyOrSellAtPriceLevel::Sell(price.clone()),
+                };
+                self.ids_to_price_level.insert(*id, price_level);
+            }
+        }
+        for hold in self.holds.values_mut().filter(|hold| hold.side == side) {
+            hold.unit_price = shift(&hold.unit_price);
+        }
+        if let Some(notional) = &mut self.notional {
+            notional.0.recount(&mut resting(&self.buys, &self.sells))
+        }
+        let sequence = self.next_sequence();
+        match self.status {
+            BookStatus::Open => Ok(self.match_crossing(side, sequence)),
+            BookStatus::Halted | BookStatus::AuctionOnly => Ok(vec![]),
+        }
+    }
+
+    /// Match each order on `taker` which crosses the book, best first and each level in queue order,
+    /// as an incoming order at its price would be matched.
+    ///
+    /// What's left of each keeps its place at the front of its level, unless it would rest crossing the levels
+    /// it skipped, in which case it rests or is cancelled as the remainder of an incoming order would be.
+    fn match_crossing(
+        &mut self,
+        taker: Side,
+        sequence: u64,
+    ) -> Vec<Trade<QuantityT, PriceT, uuid::Uuid>> {
+        let mut trades = Vec::new();
+        while matches!(self.book_state(), BookState::Locked | BookState::Crossed) {
+            let takers = self.levels_mut(taker);
+            let best = match taker {
+                Side::Buy => takers.pop_last(),
+                Side::Sell => takers.pop_first(),
+            };
+            let (taker_price, level) = best.expect("book is crossed");
+            let (remaining_level, (id, quantity)) = level.pop_front();
+            if let Some(remaining_level) = remaining_level {
+                takers.insert_uncontended(taker_price.clone(), remaining_level)
+            }
+            self.notional_left(taker, &taker_price, &quantity);
+
+            let limit = self.collared(taker, taker_price.clone());
+            let max_adverse_spread = self.max_adverse_spread.clone();
+            let mut fills = vec![];
+            self.walk_makers(
+                taker,
+                quantity.clone(),
+                &limit,
+                max_adverse_spread,
+                |fill| {
+                    fills.push(fill);
+                    ControlFlow::Continue(())
+                },
+            );
+            let remaining = match fills.last() {
+                Some(fill) => fill.taker_remaining.clone(),
+                None => quantity.clone(),
+            };
+            let resting_price = self.resting_price(
+                taker,
+                best_maker_price(self.makers(taker), taker),
+                taker_price.clone(),
+                limit,
+            );
+            if !fills.is_empty() {
+                self.capture_spread(&fills);
+                self.count_level_hits(&fills);
+                self.retire_filled_makers(taker, &fills);
+                self.record_trades(sequence, taker, &fills);
+                self.record_counterparties(id, &fills);
+                execution_metrics::trades_executed(taker, &fills);
+                let reason = match remaining.is_zero() {
+                    true => RemovalReason::Filled,
+                    false => RemovalReason::PartiallyFilled,
+                };
+                self.remove_order(OrderRemoved {
+                    id,
+                    reason,
+                    side: taker,
+                    price: taker_price.clone(),
+                    quantity: quantity - remaining.clone(),
+                });
+                trades.extend(fills.into_iter().map(|fill| Trade {
+                    sequence,
+                    taker_side: taker,
+                    maker: fill.maker,
+                    unit_price: fill.unit_price,
+                    quantity: fill.quantity,
+                }));
+            }
+            match (remaining.is_zero(), resting_price) {
+                (true, _) => {}
+                (false, Some(resting_price)) if resting_price == taker_price => {
+                    self.notional_entered(taker, &taker_price, &remaining);
+                    let takers = self.levels_mut(taker);
+                    match takers.get_mut(&taker_price) {
+                        Some(level) => level.push_front((id, remaining)),
+                        None => takers
+                            .insert_uncontended(taker_price, NonEmpty::vecdeque((id, remaining))),
+                    }
+                }
+                (false, Some(resting_price)) => {
+                    self.insert_resting(id, taker, remaining, resting_price.clone());
+                    for hold in self.holds.values_mut().filter(|hold| hold.id == id) {
+                        hold.unit_price = resting_price.clone()
+                    }
+                }
+                (false, None) => self.remove_order(OrderRemoved {
+                    id,
+                    reason: RemovalReason::Cancelled,
+                    side: taker,
+                    price: taker_price,
+                    quantity: remaining,
+                }),
+            }
+        }
+        trades
+    }
+
+    /// Repeatedly match the best order on `taker` against the other side until the book is uncrossed.
+    fn uncross(&mut self, taker: Side, sequence: u64) -> Vec<Trade<QuantityT, PriceT, uuid::Uuid>> {
+        let mut trades = Vec::new();
+        while matches!(self.book_state(), BookState::Locked | BookState::Crossed) {
+            let (takers, makers) = match taker {
+                Side::Buy => (&mut self.buys, &mut self.sells),
+                Side::Sell => (&mut self.sells, &mut self.buys),
+            };
+            let best = match taker {
+                Side::Buy => takers.pop_last(),
+                Side::Sell => takers.pop_first(),
+            };
+            let (taker_price, level) = best.expect("book is crossed");
+            let (remaining_level, (taker_id, quantity)) = level.pop_front();
+            if let Some(remaining_level) = remaining_level {
+                takers.insert_uncontended(taker_price.clone(), remaining_level)
+            }
+            let matched = match_against(makers, taker, quantity.clone(), &taker_price, |_| {
+                ControlFlow::<Infallible>::Continue(())
+            });
+            let (_, fills) = matched
+                .unwrap_or_else(|never| match never {})
+                .expect("book is crossed");
+            let executed = fills.iter().fold(QuantityT::zero(), |total, fill| {
+                total + fill.quantity.clone()
+            });
+            // `takers` is still borrowed, so this is `record_counterparties` inlined
+            self.counterparties
+                .entry(taker_id)
+                .or_default()
+                .extend(fills.iter().map(|fill| (fill.maker, fill.quantity.clone())));
+            let reason = match quantity - executed.clone() {
+                remaining if remaining.is_zero() => RemovalReason::Filled,
+                remaining => {
+                    match takers.get_mut(&taker_price) {
+                        Some(level) => level.push_front((taker_id, remaining)),
+                        None => takers.insert_uncontended(
+                            taker_price.clone(),
+                            NonEmpty::vecdeque((taker_id, remaining)),
+                        ),
+                    }
+                    RemovalReason::PartiallyFilled
+                }
+            };
+            self.notional_left(taker, &taker_price, &executed);
+            self.remove_order(OrderRemoved {
+                id: taker_id,
+                reason,
+                side: taker,
+                price: taker_price,
+                quantity: executed,
+            });
+            self.capture_spread(&fills);
+            self.count_level_hits(&fills);
+            self.retire_filled_makers(taker, &fills);
+            self.record_trades(sequence, taker, &fills);
+            execution_metrics::trades_executed(taker, &fills);
+            trades.extend(fills.into_iter().map(|fill| Trade {
+                sequence,
+                taker_side: taker,
+                maker: fill.maker,
+                unit_price: fill.unit_price,
+                quantity: fill.quantity,
+            }));
+        }
+        trades
+    }
+
+    /// Cancel `id` only if `predicate` returns `true` for it as it currently rests.
+    pub fn cancel_if(
+        &mut self,
+        id: uuid::Uuid,
+        predicate: impl FnOnce(&Order<QuantityT, PriceT, uuid::Uuid>) -> bool,
+    ) -> Result<CancelOutcome, NoSuchOrder> {
+        let order = match self.query_ref(id)? {
+            BuyOrSell::Buy {
+                quantity,
+                unit_price,
+            }
+            | BuyOrSell::Sell {
+                quantity,
+                unit_price,
+            } => Order {
+                quantity: quantity.clone(),
+                unit_price: unit_price.clone(),
+                id,
+            },
+        };
+        match predicate(&order) {
+            true => self
+                .cancel_reporting_level(id)
+                .map(|_| CancelOutcome::Cancelled),
+            false => Ok(CancelOutcome::Kept),
+        }
+    }
+
+    /// Change both the quantity and price of a resting order at once.
+    ///
+    /// Reducing only the quantity keeps the order's place in its queue.
+    /// Any other change sends it to the back of the queue at `new_price`,
+    /// trading first if that crosses the book, through as many levels as it crosses.
+    /// While the book is halted, an amendment which would cross it is [rejected](AmendOutcome::Rejected).
+    pub fn amend(
+        &mut self,
+        id: uuid::Uuid,
+        new_quantity: Positive<QuantityT>,
+        new_price: PriceT,
+    ) -> Result<AmendOutcome<QuantityT, PriceT, uuid::Uuid>, NoSuchOrder> {
+        let (side, price, quantity) = match self.query_ref(id)? {
+            BuyOrSell::Buy {
+                quantity,
+                unit_price,
+            } => (Side::Buy, unit_price.clone(), quantity.clone()),
+            BuyOrSell::Sell {
+                quantity,
+                unit_price,
+            } => (Side::Sell, unit_price.clone(), quantity.clone()),
+        };
+        let new_quantity = new_quantity.into_inner();
+        let crosses =
+            best_maker_price(self.makers(side), side).is_some_and(|maker_price| match side {
+                Side::Buy => *maker_price <= new_price,
+                Side::Sell => *maker_price >= new_price,
+            });
+        if self.status == BookStatus::Halted && crosses {
+            return Ok(AmendOutcome::Rejected);
+        }
+        // whatever rests again is accounted for as it does
+        self.notional_left(side, &price, &quantity);
+        let levels = self.levels_mut(side);
+        if new_price == price && new_quantity <= quantity {
+            let level = levels.get_mut(&price).expect("order was just queried");
+            for (it_id, it_quantity) in level.iter_mut() {
+                if *it_id == id {
+                    *it_quantity = new_quantity.clone()
+                }
+            }
+            self.notional_entered(side, &price, &new_quantity);
+            self.next_sequence();
+            return Ok(AmendOutcome::KeptPriority);
+        }
+        let level = levels.remove(&price).expect("order was just queried");
+        if let (Some(remaining_level), _) = level.pop_once_by(|(it_id, _)| *it_id == id) {
+            levels.insert_uncontended(price, remaining_level)
+        }
+        self.ids_to_price_level.remove(&id);
+
+        let limit = self.collared(side, new_price.clone());
+        let mut fills = vec![];
+        if self.status == BookStatus::Open {
+            let max_adverse_spread = self.max_adverse_spread.clone();
+            self.walk_makers(
+                side,
+                new_quantity.clone(),
+                &limit,
+                max_adverse_spread,
+                |fill| {
+                    fills.push(fill);
+                    ControlFlow::Continue(())
+                },
+            );
+        }
+        let remaining = match fills.last() {
+            Some(fill) => fill.taker_remaining.clone(),
+            None => new_quantity,
+        };
+        let resting_price = match self.status == BookStatus::Open {
+            true => self.resting_price(
+                side,
+                best_maker_price(self.makers(side), side),
+                new_price.clone(),
+                limit,
+            ),
+            false => Some(new_price.clone()),
+        };
+        let sequence = self.next_sequence();
+        if !fills.is_empty() {
+            self.capture_spread(&fills);
+            self.count_level_hits(&fills);
+            self.retire_filled_makers(side, &fills);
+            self.record_trades(sequence, side, &fills);
+            self.record_counterparties(id, &fills);
+            execution_metrics::trades_executed(side, &fills);
+        }
+        let new_price = match (remaining.is_zero(), resting_price) {
+            (true, _) => {
+                self.remove_order(OrderRemoved {
+                    id,
+                    reason: RemovalReason::Filled,
+                    side,
+                    price: new_price.clone(),
+                    quantity: fills
+                        .iter()
+                        .fold(remaining, |total, fill| total + fill.quantity.clone()),
+                });
+                new_price
+            }
+            (false, None) => {
+                self.remove_order(OrderRemoved {
+                    id,
+                    reason: RemovalReason::Cancelled,
+                    side,
+                    price: new_price,
+                    quantity: remaining,
+                });
+                return Ok(AmendOutcome::Cancelled { fills });
+            }
+            (false, Some(resting_price)) => {
+                self.insert_resting(id, side, remaining, resting_price.clone());
+                resting_price
+            }
+        };
+        // whether it rests or was filled, the order's holds are now at its new price
+        for hold in self.holds.values_mut().filter(|hold| hold.id == id) {
+            hold.unit_price = new_price.clone()
+        }
+        match fills.is_empty() {
+            true => Ok(AmendOutcome::LostPriority),
+            false => Ok(AmendOutcome::Executed { fills }),
+        }
+    }
+
+    /// Like [`unconditional_buy`](crate::api::UnconditionalOrderBookApi::unconditional_buy), but if the buy enters the book,
+    /// also reports whether it rested behind sells which were too expensive, or because there were no sells.
+    pub fn buy_reporting_rest(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> (
+        BuyEntryOrExecution<QuantityT, PriceT, uuid::Uuid>,
+        Option<RestReport<PriceT>>,
+    ) {
+        let best_opposing = self.sells.keys().next().cloned();
+        let (outcome, _) = self.match_or_rest_unconditionally(Side::Buy, quantity, unit_price);
+        let report = matches!(outcome, MatchOutcome::EnteredOrderBook { .. })
+            .then_some(RestReport { best_opposing });
+        (outcome.into_buy(), report)
+    }
+
+    /// Like [`unconditional_sell`](crate::api::UnconditionalOrderBookApi::unconditional_sell), but if the sell enters the book,
+    /// also reports whether it rested behind buys which were too cheap, or because there were no buys.
+    pub fn sell_reporting_rest(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> (
+        SellEntryOrExecution<QuantityT, PriceT, uuid::Uuid>,
+        Option<RestReport<PriceT>>,
+    ) {
+        let best_opposing = self.buys.keys().next_back().cloned();
+        let (outcome, _) = self.match_or_rest_unconditionally(Side::Sell, quantity, unit_price);
+        let report = matches!(outcome, MatchOutcome::EnteredOrderBook { .. })
+            .then_some(RestReport { best_opposing });
+        (outcome.into_sell(), report)
+    }
+
+    /// Buy `quantity` at whatever price it takes, sweeping the sells from the cheapest up until it is filled
+    /// or there are no sells left.
+    ///
+    /// Whatever isn't filled is returned rather than resting.
+    /// Nothing trades unless the book is [open](BookStatus::Open),
+    /// but [`Self::with_max_adverse_spread`] doesn't apply, since there is no price to check.
+    /// For the same reason, fills report no price improvement, so add nothing to [`Self::session_spread_captured`].
+    /// [`Self::with_price_collar`] stops the sweep at the edge of its band.
+    pub fn market_buy(
+        &mut self,
+        quantity: Positive<QuantityT>,
+    ) -> MarketExecution<QuantityT, PriceT, uuid::Uuid>
+    where
+        PriceT: ops::Mul<QuantityT, Output = PriceT> + ops::Div<QuantityT, Output = PriceT>,
+    {
+        self.market(Side::Buy, quantity.into_inner())
+    }
+
+    /// Sell `quantity` at whatever price it takes, sweeping the buys from the most generous down until it is filled
+    /// or there are no buys left.
+    ///
+    /// Whatever isn't filled is returned rather than resting.
+    /// Nothing trades unless the book is [open](BookStatus::Open),
+    /// but [`Self::with_max_adverse_spread`] doesn't apply, since there is no price to check.
+    /// For the same reason, fills report no price improvement, so add nothing to [`Self::session_spread_captured`].
+    /// [`Self::with_price_collar`] stops the sweep at the edge of its band.
+    pub fn market_sell(
+        &mut self,
+        quantity: Positive<QuantityT>,
+    ) -> MarketExecution<QuantityT, PriceT, uuid::Uuid>
+    where
+        PriceT: ops::Mul<QuantityT, Output = PriceT> + ops::Div<QuantityT, Output = PriceT>,
+    {
+        self.market(Side::Sell, quantity.into_inner())
+    }
+
+    fn market(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+    ) -> MarketExecution<QuantityT, PriceT, uuid::Uuid>
+    where
+        PriceT: ops::Mul<QuantityT, Output = PriceT> + ops::Div<QuantityT, Output = PriceT>,
+    {
+        execution_metrics::order_submitted(taker, &quantity);
+        // the worst resting price crosses every level, short of the collar
+        let makers = self.makers(taker);
+        let worst_price = match taker {
+            Side::Buy => makers.keys().next_back(),
+            Side::Sell => makers.keys().next(),
+        }
+        .filter(|_| self.status == BookStatus::Open)
+        .map(|worst_price| self.collared(taker, worst_price.clone()));
+        let mut fills = match worst_price {
+            Some(worst_price) => self.take(taker, quantity.clone(), &worst_price, None),
+            None => vec![],
+        };
+        if fills.is_empty() {
+            return MarketExecution {
+                fills,
+                filled: QuantityT::zero(),
+                average_price: None,
+                unfilled: quantity,
+            };
+        }
+        // there was no limit to improve on
+        for fill in &mut fills {
+            fill.price_improvement = None
+        }
+        let (notional, filled) = fills.iter().fold(
+            (PriceT::zero(), QuantityT::zero()),
+            |(notional, filled), fill| {
+                (
+                    notional + fill.unit_price.clone() * fill.quantity.clone(),
+                    filled + fill.quantity.clone(),
+                )
+            },
+        );
+        MarketExecution {
+            average_price: Some(notional / filled.clone()),
+            unfilled: quantity - filled.clone(),
+            filled,
+            fills,
+        }
+    }
+
+    /// An immediate-or-cancel buy, which trades like [`unconditional_buy`](crate::api::UnconditionalOrderBookApi::unconditional_buy),
+    /// but never rests.
+    ///
+    /// Returns the fills, and whatever was left over, which is discarded.
+    pub fn buy_ioc(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> (Vec<FillReport<QuantityT, PriceT, uuid::Uuid>>, QuantityT) {
+        self.immediate_or_cancel(Side::Buy, quantity.into_inner(), unit_price)
+    }
+
+    /// An immediate-or-cancel sell, which trades like [`unconditional_sell`](crate::api::UnconditionalOrderBookApi::unconditional_sell),
+    /// but never rests.
+    ///
+    /// Returns the fills, and whatever was left over, which is discarded.
+    pub fn sell_ioc(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> (Vec<FillReport<QuantityT, PriceT, uuid::Uuid>>, QuantityT) {
+        self.immediate_or_cancel(Side::Sell, quantity.into_inner(), unit_price)
+    }
+
+    fn immediate_or_cancel(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: PriceT,
+    ) -> (Vec<FillReport<QuantityT, PriceT, uuid::Uuid>>, QuantityT) {
+        execution_metrics::order_submitted(taker, &quantity);
+        if self.status != BookStatus::Open {
+            return (vec![], quantity);
+        }
+        let limit = self.collared(taker, unit_price);
+        let max_adverse_spread = self.max_adverse_spread.clone();
+        let fills = self.take(taker, quantity.clone(), &limit, max_adverse_spread);
+        self.capture_spread(&fills);
+        let discarded = match fills.last() {
+            Some(fill) => fill.taker_remaining.clone(),
+            None => quantity,
+        };
+        (fills, discarded)
+    }
+
+    /// Walk the makers for an incoming order on `taker`, as [`walk_unprotected_levels`],
+    /// forgetting the minimum quantities of those it fully executes.
+    fn walk_makers(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: &PriceT,
+        max_adverse_spread: Option<PriceT>,
+        on_fill: impl FnMut(FillReport<QuantityT, PriceT, uuid::Uuid>) -> ControlFlow<()>,
+    ) -> Option<MatchOutcome<QuantityT, PriceT, uuid::Uuid>> {
+        // taken out, since `makers_mut` borrows the whole book
+        let mut min_quantities = std::mem::take(&mut self.min_quantities);
+        let outcome = walk_unprotected_levels(
+            self.makers_mut(taker),
+            taker,
+            quantity,
+            unit_price,
+            max_adverse_spread.as_ref(),
+            &mut min_quantities,
+            on_fill,
+        );
+        self.min_quantities = min_quantities;
+        outcome
+    }
+
+    /// Trade `quantity` through every level up to `unit_price` which `max_adverse_spread` doesn't protect,
+    /// without resting what's left, whatever the [status](Self::status) of the book.
+    fn take(
+        &mut self,
+        taker: Side,
+        quantity: QuantityT,
+        unit_price: &PriceT,
+        max_adverse_spread: Option<PriceT>,
+    ) -> Vec<FillReport<QuantityT, PriceT, uuid::Uuid>> {
+        let mut fills = vec![];
+        self.walk_makers(taker, quantity, unit_price, max_adverse_spread, |fill| {
+            fills.push(fill);
+            ControlFlow::Continue(())
+        });
+        if fills.is_empty() {
+            return fills;
+        }
+        let sequence = self.next_sequence();
+        self.count_level_hits(&fills);
+        self.retire_filled_makers(taker, &fills);
+        self.record_trades(sequence, taker, &fills);
+        execution_metrics::trades_executed(taker, &fills);
+        fills
+    }
+
+    /// Like [`unconditional_buy`](crate::api::UnconditionalOrderBookApi::unconditional_buy),
+    /// but `on_trade` is called with each trade, in the order they occurred, before the outcome is returned.
+    pub fn buy_for_each_trade(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        on_trade: impl FnMut(Trade<QuantityT, PriceT, uuid::Uuid>),
+    ) -> BuyEntryOrExecution<QuantityT, PriceT, uuid::Uuid> {
+        self.match_or_rest_for_each_trade(Side::Buy, quantity, unit_price, on_trade)
+            .into_buy()
+    }
+
+    /// Like [`unconditional_sell`](crate::api::UnconditionalOrderBookApi::unconditional_sell),
+    /// but `on_trade` is called with each trade, in the order they occurred, before the outcome is returned.
+    pub fn sell_for_each_trade(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        on_trade: impl FnMut(Trade<QuantityT, PriceT, uuid::Uuid>),
+    ) -> SellEntryOrExecution<QuantityT, PriceT, uuid::Uuid> {
+        self.match_or_rest_for_each_trade(Side::Sell, quantity, unit_price, on_trade)
+            .into_sell()
+    }
+
+    fn match_or_rest_for_each_trade(
+        &mut self,
+        taker: Side,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        on_trade: impl FnMut(Trade<QuantityT, PriceT, uuid::Uuid>),
+    ) -> MatchOutcome<QuantityT, PriceT, uuid::Uuid> {
+        match self.match_or_rest_streaming(
+            taker,
+            quantity.into_inner(),
+            unit_price,
+            TakerLimits::default(),
+            |_, _| ControlFlow::<Infallible>::Continue(()),
+            on_trade,
+        ) {
+            Ok((outcome, _)) => outcome,
+            Err(never) => match never {},
+        }
+    }
+
+    fn match_or_rest_unconditionally(
+        &mut self,
+        taker: Side,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> Matched<QuantityT, PriceT, uuid::Uuid> {
+        match self.match_or_rest(
+            taker,
+            quantity.into_inner(),
+            unit_price,
+            TakerLimits::default(),
+            |_, _| ControlFlow::<Infallible>::Continue(()),
+        ) {
+            Ok(matched) => matched,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`OrderBookApi::cancel`], but also reports whether the order was the last at its price level.
+    pub fn cancel_reporting_level(&mut self, id: uuid::Uuid) -> Result<CancelReport, NoSuchOrder> {
+        let price_level = self.ids_to_price_level.remove(&id).ok_or(NoSuchOrder)?;
+        let (side, price) = match &price_level {
+            BuyOrSellAtPriceLevel::Buy(price) => (Side::Buy, price.clone()),
+            BuyOrSellAtPriceLevel::Sell(price) => (Side::Sell, price.clone()),
+        };
+        let Some(quantity) = find_quantity(self.levels(side), &price, &id).cloned() else {
+            return Err(self.stale_index(&id, &price_level));
+        };
+        self.next_sequence();
+        self.remove_order(OrderRemoved {
+            id,
+            reason: RemovalReason::Cancelled,
+            side,
+            price: price.clone(),
+            quantity,
+        });
+        let level_now_empty = !self.levels(side).contains_key(&price);
+        execution_metrics::order_cancelled(side);
+        Ok(CancelReport { level_now_empty })
+    }
+}
+
+impl<QuantityT, PriceT> FillReportingOrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug + Measurable,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero + Measurable,
+{
+    #[cfg_attr(
+        not(feature = "no-tracing"),
+        tracing::instrument(skip(self, condition), ret)
+    )]
+    fn conditional_buy_reporting_fills<BuyAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(
+            ConditionalBuyArgs<'_, uuid::Uuid>,
+        ) -> std::ops::ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyWithFills<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
+        self.match_or_rest(
+            Side::Buy,
+            quantity.into_inner(),
+            unit_price,
+            TakerLimits::default(),
+            |_, seller_id| condition(ConditionalBuyArgs { seller_id }),
+        )
+        .map(|(outcome, fills)| (outcome.into_buy(), fills))
+    }
+
+    #[cfg_attr(
+        not(feature = "no-tracing"),
+        tracing::instrument(skip(self, condition), ret)
+    )]
+    fn conditional_sell_reporting_fills<SellAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(
+            ConditionalSellArgs<'_, uuid::Uuid>,
+        ) -> std::ops::ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellWithFills<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
+        self.match_or_rest(
+            Side::Sell,
+            quantity.into_inner(),
+            unit_price,
+            TakerLimits::default(),
+            |_, buyer_id| condition(ConditionalSellArgs { buyer_id }),
+        )
+        .map(|(outcome, fills)| (outcome.into_sell(), fills))
+    }
+}
+
+impl<QuantityT, PriceT> RestWithIdOrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug + Measurable,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero + Measurable,
+{
+    #[cfg_attr(not(feature = "no-tracing"), tracing::instrument(skip(self), ret))]
+    fn rest_with_id(
+        &mut self,
+        id: uuid::Uuid,
+        side: Side,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+    ) -> Result<(), DuplicateId> {
+        if self.ids_to_price_level.contains_key(&id) {
+            return Err(DuplicateId);
+        }
+        self.next_sequence();
+        self.insert_resting(id, side, quantity.into_inner(), unit_price);
+        Ok(())
+    }
+}
+
+/// Rests each order with a fresh id, without matching.
+impl<QuantityT, PriceT> Extend<(Side, Positive<QuantityT>, PriceT)>
+    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Clone,
+    PriceT: Clone + Ord,
+{
+    fn extend<T: IntoIterator<Item = (Side, Positive<QuantityT>, PriceT)>>(&mut self, iter: T) {
+        for (side, quantity, unit_price) in iter {
+            self.next_sequence();
+            self.rest(side, quantity.into_inner(), unit_price);
+        }
+    }
+}
+
+/// Rests each order with a fresh id, without matching.
+impl<QuantityT, PriceT> FromIterator<(Side, Positive<QuantityT>, PriceT)>
+    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Clone,
+    PriceT: Clone + Ord,
+{
+    fn from_iter<T: IntoIterator<Item = (Side, Positive<QuantityT>, PriceT)>>(iter: T) -> Self {
+        Self::default().tap_mut(|it| it.extend(iter))
+    }
+}
+
+impl<QuantityT, PriceT> OrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug + Measurable,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero + Measurable,
+{
+    fn conditional_buy<BuyAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(
+            ConditionalBuyArgs<'_, uuid::Uuid>,
+        ) -> std::ops::ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyEntryOrExecution<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
+        self.conditional_buy_reporting_fills(quantity, unit_price, condition)
+            .map(|(entry_or_exc, _fills)| entry_or_exc)
+    }
+
+    fn conditional_sell<SellAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(
+            ConditionalSellArgs<'_, uuid::Uuid>,
+        ) -> std::ops::ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellEntryOrExecution<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
+        self.conditional_sell_reporting_fills(quantity, unit_price, condition)
+            .map(|(entry_or_exc, _fills)| entry_or_exc)
+    }
+
+    #[cfg_attr(not(feature = "no-tracing"), tracing::instrument(skip(self), ret))]
+    fn query(&self, id: uuid::Uuid) -> Result<BuyOrSell<QuantityT, PriceT>, NoSuchOrder> {
+        self.query_ref(id).map(|order| match order {
+            BuyOrSell::Buy {
+                quantity,
+                unit_price,
+            } => BuyOrSell::Buy {
+                quantity: quantity.clone(),
+                unit_price: unit_price.clone(),
+            },
+            BuyOrSell::Sell {
+                quantity,
+                unit_price,
+            } => BuyOrSell::Sell {
+                quantity: quantity.clone(),
+                unit_price: unit_price.clone(),
+            },
+        })
+    }
+
+    #[cfg_attr(not(feature = "no-tracing"), tracing::instrument(skip(self), ret))]
+    fn cancel(&mut self, id: uuid::Uuid) -> Result<Cancelled, NoSuchOrder> {
+        self.cancel_reporting_level(id).map(|_| Cancelled)
+    }
+}
+
+impl<QuantityT, PriceT> ReportingOrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for PriceLevelBTreeOrderBook<QuantityT, PriceT, uuid::Uuid>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug + Measurable,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero + Measurable,
+{
+    fn iter_buys(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, uuid::Uuid>> + '_ {
+        self.buys.iter().rev().flat_map(|(price, level)| {
+            level.iter().map(|(id, quantity)| Order {
+                quantity: quantity.clone(),
+                unit_price: price.clone(),
+                id: *id,
+            })
+        })
+    }
+
+    fn iter_sells(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, uuid::Uuid>> + '_ {
+        self.sells.iter().flat_map(|(price, level)| {
+            level.iter().map(|(id, quantity)| Order {
+                quantity: quantity.clone(),
+                unit_price: price.clone(),
+                id: *id,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BookState, OrderStatus, PriceLevelBTreeOrderBook, StaleIndexPolicy};
+    use crate::util::NonEmpty;
+    use crate::{
+        api::{Side, UnconditionalOrderBookApi as _},
+        test_suite::do_test_suite,
+    };
+    use numwit::Positive;
+    use pretty_assertions::assert_eq;
+    use tap::Tap as _;
+
+    do_test_suite! {PriceLevelBTreeOrderBook<usize, usize, uuid::Uuid> {
+        default_is_empty,
+        add_query_remove_single_buy_order,
+        add_query_remove_single_sell_order,
+        single_resident_buy_is_fully_executed,
+        single_resident_sell_is_fully_executed,
+        buys_reported_with_price_time_priority,
+        sells_reported_with_price_time_priority,
+        buys_execute_with_price_time_priority,
+        sells_execute_with_price_time_priority,
+        fill_reports_partially_executed_maker,
+        fill_reports_fully_executed_maker,
+        aborted_conditional_buy_leaves_book_unchanged,
+        aborted_conditional_sell_leaves_book_unchanged,
+        buy_consumes_successive_sells_at_one_price,
+        sell_consumes_successive_buys_at_one_price,
+        partially_executed_buy_keeps_its_place_in_buys,
+        partially_executed_sell_keeps_its_place_in_sells,
+        partially_filled_buy_remainder_stays_a_buy,
+        cancelling_middle_sell_keeps_time_priority,
+        fill_reports_price_improvement,
+        try_buy_and_sell_reject_zero_quantity,
+        buy_at_resting_price_joins_back_of_level,
+        sell_at_resting_price_joins_back_of_level,
+    }}
+
+    fn book() -> PriceLevelBTreeOrderBook<usize, usize, uuid::Uuid> {
+        PriceLevelBTreeOrderBook::default()
+    }
+
+    fn positive(quantity: usize) -> Positive<usize> {
+        Positive::new(quantity).unwrap()
+    }
+
+    #[test]
+    fn level_queue_reports_partially_filled_front_order() {
+        let mut order_book = book();
+        let first = order_book
+            .unconditional_sell(positive(3), 10)
+            .into_entered_order_book()
+            .unwrap();
+        let second = order_book
+            .unconditional_sell(positive(2), 10)
+            .into_entered_order_book()
+            .unwrap();
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(
+            Some(vec![(first, 2), (second, 2)]),
+            order_book.level_queue(Side::Sell, &10)
+        );
+        assert_eq!(None, order_book.level_queue(Side::Sell, &11));
+        assert_eq!(None, order_book.level_queue(Side::Buy, &10));
+    }
+
+    #[test]
+    fn available_to_buy_only_counts_affordable_levels() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_sell(positive(4), 12);
+        assert_eq!(0, order_book.available_to_buy(&9));
+        assert_eq!(3, order_book.available_to_buy(&11));
+        assert_eq!(7, order_book.available_to_buy(&12));
+    }
+
+    #[test]
+    fn available_to_sell_only_counts_generous_levels() {
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(4), 10);
+        order_book.unconditional_buy(positive(1), 12);
+        order_book.unconditional_buy(positive(2), 12);
+        assert_eq!(0, order_book.available_to_sell(&13));
+        assert_eq!(3, order_book.available_to_sell(&11));
+        assert_eq!(7, order_book.available_to_sell(&10));
+    }
+
+    #[test]
+    fn impact_count_includes_partially_executed_order() {
+        let mut order_book = book();
+        for quantity in [2, 2, 2, 2] {
+            order_book.unconditional_sell(positive(quantity), 10);
+        }
+        assert_eq!(0, order_book.impact_count(Side::Buy, 5, &9));
+        assert_eq!(3, order_book.impact_count(Side::Buy, 5, &10));
+        assert_eq!(2, order_book.impact_count(Side::Buy, 4, &10));
+        assert_eq!(0, order_book.impact_count(Side::Sell, 5, &10));
+        order_book.unconditional_sell(positive(2), 11);
+        assert_eq!(4, order_book.impact_count(Side::Buy, 9, &10));
+        assert_eq!(5, order_book.impact_count(Side::Buy, 9, &11));
+    }
+
+    #[test]
+    fn market_buy_sweeps_every_level() {
+        use super::MarketExecution;
+        use crate::api::{FillReport, ReportingOrderBookApi as _};
+
+        let mut order_book = book();
+        let first = *order_book
+            .unconditional_sell(positive(1), 10)
+            .as_entered_order_book()
+            .unwrap();
+        let second = *order_book
+            .unconditional_sell(positive(3), 12)
+            .as_entered_order_book()
+            .unwrap();
+        order_book.unconditional_buy(positive(1), 8);
+        assert_eq!(
+            MarketExecution {
+                fills: vec![
+                    FillReport {
+                        maker: first,
+                        unit_price: 10,
+                        quantity: 1,
+                        taker_remaining: 5,
+                        maker_remaining: 0,
+                        price_improvement: None,
+                    },
+                    FillReport {
+                        maker: second,
+                        unit_price: 12,
+                        quantity: 3,
+                        taker_remaining: 2,
+                        maker_remaining: 0,
+                        price_improvement: None,
+                    },
+                ],
+                filled: 4,
+                average_price: Some(11),
+                unfilled: 2,
+            },
+            order_book.market_buy(positive(6))
+        );
+        assert!(order_book.sells().is_empty());
+        assert_eq!(1, order_book.buys().len());
+        assert_eq!(0, order_book.session_spread_captured());
+    }
+
+    #[test]
+    fn price_collar_stops_sweeps() {
+        use super::{CollarRemainder, PriceCollar};
+        use crate::api::{BuyOrSell, OrderBookApi as _};
+
+        // the last trade is at 100, so nothing trades above 103
+        let collared = |remainder| {
+            let mut order_book = book().with_price_collar(PriceCollar { band: 3, remainder });
+            order_book.unconditional_sell(positive(1), 100);
+            order_book.unconditional_buy(positive(1), 100);
+            for price in [101, 103, 106] {
+                order_book.unconditional_sell(positive(2), price);
+            }
+            order_book
+        };
+
+        let mut order_book = collared(CollarRemainder::Cancel);
+        let execution = order_book.market_buy(positive(10));
+        let prices = execution.fills.iter().map(|fill| fill.unit_price);
+        assert_eq!(vec![101, 103], prices.collect::<Vec<_>>());
+        assert_eq!(6, execution.unfilled);
+        assert_eq!(Some(&103), order_book.last_trade_price());
+
+        let mut order_book = collared(CollarRemainder::Cancel);
+        let executed = order_book.unconditional_buy(positive(5), 110);
+        assert_eq!(Some(&1), executed.as_seller_fully_executed().map(|it| it.2));
+        assert_eq!(vec![106], order_book.ask_prices());
+        assert!(order_book.bid_prices().is_empty());
+
+        let mut order_book = collared(CollarRemainder::Rest);
+        let entered = order_book.unconditional_buy(positive(5), 110);
+        let (id, ..) = entered.as_partially_executed_then_entered().unwrap();
+        assert_eq!(
+            Ok(BuyOrSell::Buy {
+                quantity: 1,
+                unit_price: 103
+            }),
+            order_book.query(*id)
+        );
+        assert_eq!(BookState::Normal, order_book.book_state());
+    }
+
+    #[test]
+    fn market_order_against_empty_side_is_unfilled() {
+        use super::MarketExecution;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        let before = order_book.current_sequence();
+        assert_eq!(
+            MarketExecution {
+                fills: vec![],
+                filled: 0,
+                average_price: None,
+                unfilled: 3,
+            },
+            order_book.market_buy(positive(3))
+        );
+        assert_eq!(before, order_book.current_sequence());
+        assert_eq!(
+            MarketExecution {
+                fills: vec![],
+                filled: 0,
+                average_price: None,
+                unfilled: 3,
+            },
+            book().market_sell(positive(3))
+        );
+    }
+
+    #[test]
+    fn partially_filled_ioc_buy_does_not_rest() {
+        use crate::api::ReportingOrderBookApi as _;
+
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_sell(positive(2), 12);
+        let (fills, discarded) = order_book.buy_ioc(positive(5), 11);
+        assert_eq!(
+            vec![(10, 2)],
+            fills
+                .iter()
+                .map(|fill| (fill.unit_price, fill.quantity))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, discarded);
+        assert!(order_book.buys().is_empty());
+        assert_eq!(1, order_book.sells().len());
+
+        let (fills, discarded) = order_book.sell_ioc(positive(1), 11);
+        assert!(fills.is_empty());
+        assert_eq!(1, discarded);
+        assert_eq!(1, order_book.order_count());
+    }
+
+    #[test]
+    fn buy_walks_levels_and_rests_remainder() {
+        use crate::api::{
+            BuyEntryOrExecution, FillReport, FillReportingOrderBookApi as _, Order,
+            ReportingOrderBookApi as _,
+        };
+        use std::ops::ControlFlow;
+
+        let mut order_book = book();
+        let first = *order_book
+            .unconditional_sell(positive(1), 10)
+            .as_entered_order_book()
+            .unwrap();
+        let second = *order_book
+            .unconditional_sell(positive(2), 11)
+            .as_entered_order_book()
+            .unwrap();
+        order_book.unconditional_sell(positive(1), 13);
+        let (outcome, fills) = order_book
+            .conditional_buy_reporting_fills(positive(5), 12, |_| ControlFlow::<()>::Continue(()))
+            .unwrap();
+        let BuyEntryOrExecution::PartiallyExecutedThenEntered { id, .. } = outcome else {
+            panic!("unexpected outcome {outcome:?}")
+        };
+        assert_eq!(
+            BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                id,
+                seller: second,
+                spread: Some(positive(1)),
+                buyers_remaining: 2,
+            },
+            outcome
+        );
+        assert_eq!(
+            vec![
+                FillReport {
+                    maker: first,
+                    unit_price: 10,
+                    quantity: 1,
+                    taker_remaining: 4,
+                    maker_remaining: 0,
+                    price_improvement: Some(positive(2)),
+                },
+                FillReport {
+                    maker: second,
+                    unit_price: 11,
+                    quantity: 2,
+                    taker_remaining: 2,
+                    maker_remaining: 0,
+                    price_improvement: Some(positive(1)),
+                },
+            ],
+            fills
+        );
+        assert_eq!(
+            vec![Order {
+                quantity: 2,
+                unit_price: 12,
+                id
+            }],
+            order_book.buys()
+        );
+        assert_eq!(1, order_book.sells().len());
+    }
+
+    #[test]
+    fn sweep_trades_are_in_queue_order() {
+        use crate::api::FillReportingOrderBookApi as _;
+
+        let mut order_book = book().with_recent_trades_capacity(8);
+        let makers = [3, 1, 4, 1].map(|quantity| {
+            let entered = order_book.unconditional_sell(positive(quantity), 10);
+            *entered.as_entered_order_book().unwrap()
+        });
+        let (_, fills) = order_book
+            .conditional_buy_reporting_fills(positive(8), 10, |_| {
+                std::ops::ControlFlow::<std::convert::Infallible>::Continue(())
+            })
+            .unwrap();
+        assert_eq!(
+            vec![(makers[0], 3), (makers[1], 1), (makers[2], 4)],
+            fills
+                .iter()
+                .map(|fill| (fill.maker, fill.quantity))
+                .collect::<Vec<_>>()
+        );
+        let trades = order_book.recent_trades(8);
+        assert_eq!(
+            makers[..3].to_vec(),
+            trades
+                .iter()
+                .rev()
+                .map(|trade| trade.maker)
+                .collect::<Vec<_>>()
+        );
+        assert!(trades
+            .iter()
+            .all(|trade| trade.sequence == trades[0].sequence));
+        assert_eq!(
+            vec![(makers[3], 1)],
+            order_book.level_queue(Side::Sell, &10).unwrap()
+        );
+    }
+
+    #[test]
+    fn for_each_trade_sees_each_maker_in_order() {
+        let mut order_book = book();
+        let makers = [2, 2, 2].map(|quantity| {
+            let entered = order_book.unconditional_sell(positive(quantity), 10);
+            *entered.as_entered_order_book().unwrap()
+        });
+        let mut seen = vec![];
+        let outcome = order_book.buy_for_each_trade(positive(5), 10, |trade| {
+            seen.push((trade.maker, trade.quantity))
+        });
+        assert_eq!(vec![(makers[0], 2), (makers[1], 2), (makers[2], 1)], seen);
+        assert_eq!(
+            Some(&makers[2]),
+            outcome
+                .as_buyer_fully_executed()
+                .map(|(seller, _, _)| seller)
+        );
+    }
+
+    #[test]
+    fn for_each_trade_is_called_as_each_maker_is_consumed() {
+        let mut order_book = book().with_recent_trades_capacity(8);
+        for _ in 0..3 {
+            order_book.unconditional_sell(positive(2), 10);
+        }
+        let mut streamed = vec![];
+        let mut calls = 0;
+        let outcome = order_book.buy_for_each_trade(positive(6), 10, |trade| {
+            calls += 1;
+            streamed.push(trade)
+        });
+        // every call was made before the outcome came back
+        assert_eq!(3, calls);
+        assert!(outcome.as_mutual_full_execution().is_some());
+        // and each carries the sequence the book went on to record it under
+        assert_eq!(
+            order_book
+                .recent_trades(3)
+                .tap_mut(|trades| trades.reverse()),
+            streamed
+        );
+    }
+
+    #[test]
+    fn every_removal_is_reported() {
+        use super::{OrderRemoved, RemovalReason};
+        use crate::api::OrderBookApi as _;
+
+        let mut order_book = book().with_removals_capacity(8);
+        let entered = [(3, 10), (4, 9), (5, 8)].map(|(quantity, price)| {
+            let entered = order_book.unconditional_buy(positive(quantity), price);
+            *entered.as_entered_order_book().unwrap()
+        });
+        order_book.unconditional_sell(positive(3), 10);
+        order_book.unconditional_sell(positive(1), 9);
+        order_book.cancel(entered[1]).unwrap();
+        order_book.trim_to_levels(0);
+        order_book.unconditional_sell(positive(2), 12);
+        order_book.for_each_order_at_mut(Side::Sell, &12, |_, quantity| *quantity = 0);
+        // a rejected order never rested, so isn't removed
+        order_book.halt();
+        order_book.unconditional_buy(positive(6), 12);
+
+        let removals = order_book.recent_removals(8);
+        let removed = |id, reason, side, price, quantity| OrderRemoved {
+            id,
+            reason,
+            side,
+            price,
+            quantity,
+        };
+        assert_eq!(
+            (RemovalReason::Cancelled, Side::Sell, 12, 2),
+            (
+                removals[0].reason,
+                removals[0].side,
+                removals[0].price,
+                removals[0].quantity
+            )
+        );
+        assert_eq!(
+            vec![
+                removed(entered[2], RemovalReason::Evicted, Side::Buy, 8, 5),
+                removed(entered[1], RemovalReason::Cancelled, Side::Buy, 9, 3),
+                removed(entered[1], RemovalReason::PartiallyFilled, Side::Buy, 9, 1),
+                removed(entered[0], RemovalReason::Filled, Side::Buy, 10, 3),
+            ],
+            removals[1..]
+        );
+    }
+
+    #[test]
+    fn recent_trades_keeps_the_most_recent() {
+        use crate::api::Trade;
+
+        let mut order_book = book().with_recent_trades_capacity(2);
+        let makers = [10, 11, 12].map(|price| {
+            let entered = order_book.unconditional_sell(positive(1), price);
+            *entered.as_entered_order_book().unwrap()
+        });
+        for _ in makers {
+            order_book.unconditional_buy(positive(1), 12);
+        }
+        let trade = |maker, unit_price, sequence| Trade {
+            sequence,
+            taker_side: Side::Buy,
+            maker,
+            unit_price,
+            quantity: 1,
+        };
+        assert_eq!(
+            vec![trade(makers[2], 12, 6), trade(makers[1], 11, 5)],
+            order_book.recent_trades(5)
+        );
+        assert_eq!(vec![trade(makers[2], 12, 6)], order_book.recent_trades(1));
+    }
+
+    #[test]
+    fn sequence_advances_once_per_change() {
+        use crate::api::{OrderBookApi as _, RestWithIdOrderBookApi as _};
+
+        let mut order_book = book();
+        assert_eq!(0, order_book.current_sequence());
+        let entered = order_book.unconditional_buy(positive(2), 10);
+        let id = *entered.as_entered_order_book().unwrap();
+        assert_eq!(1, order_book.current_sequence());
+        order_book.unconditional_sell(positive(1), 10);
+        assert_eq!(2, order_book.current_sequence());
+        let _ = order_book.conditional_sell(positive(1), 10, |_| std::ops::ControlFlow::Break(()));
+        assert_eq!(2, order_book.current_sequence());
+        order_book.cancel(id).unwrap();
+        assert_eq!(3, order_book.current_sequence());
+        order_book.cancel(id).unwrap_err();
+        assert_eq!(3, order_book.current_sequence());
+        order_book
+            .rest_with_id(uuid::Uuid::new_v4(), Side::Sell, positive(1), 12)
+            .unwrap();
+        assert_eq!(4, order_book.current_sequence());
+    }
+
+    #[test]
+    fn quantity_to_move_best_price_counts_levels_strictly_before_target() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(2), 12);
+        order_book.unconditional_buy(positive(4), 8);
+        order_book.unconditional_buy(positive(8), 6);
+        assert_eq!(1, order_book.quantity_to_move_ask_to(&11));
+        assert_eq!(1, order_book.quantity_to_move_ask_to(&12));
+        assert_eq!(3, order_book.quantity_to_move_ask_to(&13));
+        assert_eq!(4, order_book.quantity_to_move_bid_to(&7));
+        assert_eq!(4, order_book.quantity_to_move_bid_to(&6));
+        assert_eq!(0, order_book.quantity_to_move_bid_to(&8));
+    }
+
+    #[test]
+    fn depth_conflated_sums_levels_within_a_bucket() {
+        use crate::api::DepthSnapshot;
+
+        let mut order_book = book();
+        for (quantity, price) in [(1, 20), (2, 21), (4, 24), (8, 25)] {
+            order_book.unconditional_sell(positive(quantity), price);
+        }
+        for (quantity, price) in [(1, 19), (2, 15), (4, 14), (8, 9)] {
+            order_book.unconditional_buy(positive(quantity), price);
+        }
+        assert_eq!(
+            DepthSnapshot {
+                bids: vec![(15, 1 + 2), (10, 4)],
+                asks: vec![(20, 1 + 2 + 4), (25, 8)],
+            },
+            order_book.depth_conflated(positive(5), 2)
+        );
+    }
+
+    #[test]
+    fn rest_with_id_rejects_duplicate_id() {
+        use crate::api::{BuyOrSell, DuplicateId, OrderBookApi as _, RestWithIdOrderBookApi as _};
+        use uuid::Uuid;
+
+        let mut order_book = book();
+        let id = Uuid::new_v4();
+        assert_eq!(
+            Ok(()),
+            order_book.rest_with_id(id, Side::Buy, positive(1), 10)
+        );
+        assert_eq!(
+            Err(DuplicateId),
+            order_book.rest_with_id(id, Side::Sell, positive(2), 20)
+        );
+        assert_eq!(
+            Ok(BuyOrSell::Buy {
+                quantity: 1,
+                unit_price: 10
+            }),
+            order_book.query(id)
+        );
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn dry_run_reports_fills_without_trading() {
+        use crate::api::{FillReportingOrderBookApi as _, ReportingOrderBookApi as _};
+        use std::ops::ControlFlow;
+
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_buy(positive(2), 8);
+        let before = order_book.snapshot();
+        let dry_buy = order_book.dry_run_buy(positive(2), 10);
+        let dry_sell = order_book.dry_run_sell(positive(1), 8);
+        assert_eq!(before, order_book.snapshot());
+        assert_eq!(
+            Ok(dry_buy),
+            order_book.conditional_buy_reporting_fills(positive(2), 10, |_| {
+                ControlFlow::<()>::Continue(())
+            })
+        );
+        assert_eq!(
+            Ok(dry_sell),
+            order_book.conditional_sell_reporting_fills(positive(1), 8, |_| {
+                ControlFlow::<()>::Continue(())
+            })
+        );
+    }
+
+    #[test]
+    fn resolve_crosses_trades_at_resting_ask_prices() {
+        use crate::api::Trade;
+
+        let mut order_book = [
+            (Side::Buy, positive(2), 12),
+            (Side::Buy, positive(1), 11),
+            (Side::Sell, positive(1), 10),
+            (Side::Sell, positive(3), 11),
+        ]
+        .into_iter()
+        .collect::<PriceLevelBTreeOrderBook<_, _, _>>();
+        assert_eq!(BookState::Crossed, order_book.book_state());
+        let asks = [10, 11].map(|price| order_book.level_queue(Side::Sell, &price).unwrap()[0].0);
+        let trade = |maker, unit_price| Trade {
+            sequence: 5,
+            taker_side: Side::Buy,
+            maker,
+            unit_price,
+            quantity: 1,
+        };
+        assert_eq!(
+            vec![trade(asks[0], 10), trade(asks[1], 11), trade(asks[1], 11)],
+            order_book.resolve_crosses()
+        );
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert!(order_book.bid_prices().is_empty());
+        assert_eq!(
+            Some(vec![(asks[1], 1)]),
+            order_book.level_queue(Side::Sell, &11)
+        );
+        assert!(order_book.resolve_crosses().is_empty());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn zeroed_quantity_removes_only_that_order() {
+        use crate::api::OrderBookApi as _;
+
+        let mut order_book = book();
+        let [first, second, third] = [1, 2, 3].map(|quantity| {
+            let entered = order_book.unconditional_buy(positive(quantity), 10);
+            *entered.as_entered_order_book().unwrap()
+        });
+        order_book.for_each_order_at_mut(Side::Buy, &10, |id, quantity| match id == second {
+            true => *quantity = 0,
+            false => *quantity *= 10,
+        });
+        assert_eq!(
+            Some(vec![(first, 10), (third, 30)]),
+            order_book.level_queue(Side::Buy, &10)
+        );
+        assert!(order_book.query(second).is_err());
+        assert_eq!(Ok(()), order_book.validate());
+        order_book.for_each_order_at_mut(Side::Buy, &10, |_, quantity| *quantity = 0);
+        assert!(order_book.bid_prices().is_empty());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn shifted_bids_trade_with_asks_they_cross() {
+        use super::ShiftDirection;
+        use crate::api::{OrderBookApi as _, Trade};
+
+        let mut order_book = book();
+        let [low, high] = [8, 9].map(|price| {
+            let entered = order_book.unconditional_buy(positive(1), price);
+            *entered.as_entered_order_book().unwrap()
+        });
+        let entered = order_book.unconditional_sell(positive(3), 11);
+        let ask = *entered.as_entered_order_book().unwrap();
+        assert_eq!(
+            vec![Trade {
+                sequence: 4,
+                taker_side: Side::Buy,
+                maker: ask,
+                unit_price: 11,
+                quantity: 1,
+            }],
+            order_book
+                .shift_prices(Side::Buy, ShiftDirection::Up, 2)
+                .unwrap()
+        );
+        assert!(order_book.level_queue(Side::Buy, &11).is_none());
+        assert_eq!(Some(vec![(low, 1)]), order_book.level_queue(Side::Buy, &10));
+        assert_eq!(
+            Some(vec![(ask, 2)]),
+            order_book.level_queue(Side::Sell, &11)
+        );
+        assert!(order_book.query(high).is_err());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn shifting_prices_is_checked_in_either_direction() {
+        use super::{PriceOutOfRange, ShiftDirection};
+        use crate::api::{OrderBookApi as _, Trade};
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        let ask = *order_book
+            .unconditional_sell(positive(1), 10)
+            .as_entered_order_book()
+            .unwrap();
+        order_book.unconditional_sell(positive(1), usize::MAX);
+        assert_eq!(
+            Err(PriceOutOfRange),
+            order_book.shift_prices(Side::Sell, ShiftDirection::Up, 1)
+        );
+        assert_eq!(
+            Err(PriceOutOfRange),
+            order_book.shift_prices(Side::Buy, ShiftDirection::Down, 9)
+        );
+        assert_eq!(Some(8), order_book.highest_bid_price());
+        assert_eq!(Some(10), order_book.lowest_ask_price());
+        assert_eq!(Some(usize::MAX), order_book.highest_ask_price());
+
+        assert_eq!(
+            Ok(vec![Trade {
+                sequence: 4,
+                taker_side: Side::Sell,
+                maker: order_book.level_queue(Side::Buy, &8).unwrap()[0].0,
+                unit_price: 8,
+                quantity: 1,
+            }]),
+            order_book.shift_prices(Side::Sell, ShiftDirection::Down, 3)
+        );
+        assert!(order_book.query(ask).is_err());
+        assert_eq!(Some(usize::MAX - 3), order_book.lowest_ask_price());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn shifted_prices_only_trade_in_an_open_book_and_within_its_protections() {
+        use super::ShiftDirection;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.halt();
+        assert_eq!(
+            Ok(vec![]),
+            order_book.shift_prices(Side::Buy, ShiftDirection::Up, 3)
+        );
+        assert_eq!(BookState::Crossed, order_book.book_state());
+        assert_eq!(1, order_book.resume().len());
+        assert_eq!(Ok(()), order_book.validate());
+
+        let mut order_book = book().with_max_adverse_spread(1);
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_sell(positive(1), 10);
+        // 12 is more than 1 through the ask at 10, which is protected, so the bid can't rest crossing it
+        assert_eq!(
+            Ok(vec![]),
+            order_book.shift_prices(Side::Buy, ShiftDirection::Up, 4)
+        );
+        assert_eq!(1, order_book.order_count());
+        assert_eq!(1, order_book.available_to_buy(&10));
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn level_hit_count_counts_matched_makers_at_each_price() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_sell(positive(1), 11);
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(3, order_book.level_hit_count(&10));
+        assert_eq!(0, order_book.level_hit_count(&11));
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(4, order_book.level_hit_count(&10));
+        assert_eq!(0, order_book.level_hit_count(&11));
+    }
+
+    #[test]
+    fn session_spread_captured_sums_each_execution() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(1), 11);
+        order_book.unconditional_sell(positive(3), 12);
+        assert_eq!(0, order_book.session_spread_captured());
+        order_book.unconditional_buy(positive(1), 12);
+        order_book.unconditional_buy(positive(1), 12);
+        order_book.unconditional_buy(positive(1), 12);
+        order_book.unconditional_buy(positive(2), 14);
+        assert_eq!(2 + 1 + 2, order_book.session_spread_captured());
+        order_book.reset_session();
+        assert_eq!(0, order_book.session_spread_captured());
+    }
+
+    #[test]
+    fn session_spread_captured_counts_every_level_of_a_sweep() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(1), 11);
+        order_book.unconditional_sell(positive(1), 11);
+        order_book.unconditional_buy(positive(3), 12);
+        assert_eq!(2 + 1 + 1, order_book.session_spread_captured());
+    }
+
+    #[test]
+    fn held_quantity_is_not_matched() {
+        use super::HoldError;
+        use crate::api::{BuyOrSell, OrderBookApi as _};
+
+        let mut order_book = book();
+        let entered = order_book.unconditional_sell(positive(5), 10);
+        let id = *entered.as_entered_order_book().unwrap();
+        assert_eq!(
+            Err(HoldError::InsufficientQuantity),
+            order_book.hold(id, positive(5))
+        );
+        let token = order_book.hold(id, positive(3)).unwrap();
+        let (_, fills) = order_book.dry_run_buy(positive(4), 10);
+        assert_eq!(2, fills.iter().map(|fill| fill.quantity).sum::<usize>());
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(
+            Ok(BuyOrSell::Sell {
+                quantity: 1,
+                unit_price: 10
+            }),
+            order_book.query(id)
+        );
+        order_book.release(token).unwrap();
+        assert_eq!(
+            Ok(BuyOrSell::Sell {
+                quantity: 4,
+                unit_price: 10
+            }),
+            order_book.query(id)
+        );
+        let token = order_book.hold(id, positive(2)).unwrap();
+        assert_eq!(Ok(2), order_book.commit(token));
+        assert!(order_book.release(token).is_err());
+        assert_eq!(
+            Ok(BuyOrSell::Sell {
+                quantity: 2,
+                unit_price: 10
+            }),
+            order_book.query(id)
+        );
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn holds_outlive_only_filled_orders() {
+        use super::{OrderRemoved, ReleaseError, RemovalReason};
+        use crate::api::OrderBookApi as _;
+
+        let mut order_book = book().with_removals_capacity(8);
+        let entered = order_book.unconditional_sell(positive(3), 10);
+        let id = *entered.as_entered_order_book().unwrap();
+        let token = order_book.hold(id, positive(2)).unwrap();
+        order_book.unconditional_buy(positive(1), 10);
+        assert!(order_book.query(id).is_err());
+        assert_eq!(Err(ReleaseError::NoSuchOrder), order_book.release(token));
+        assert!(order_book.is_empty());
+        assert_eq!(Ok(2), order_book.commit(token));
+        assert_eq!(
+            vec![OrderRemoved {
+                id,
+                reason: RemovalReason::Committed,
+                side: Side::Sell,
+                price: 10,
+                quantity: 2,
+            }],
+            order_book.recent_removals(1)
+        );
+
+        let entered = order_book.unconditional_sell(positive(3), 10);
+        let id = *entered.as_entered_order_book().unwrap();
+        let token = order_book.hold(id, positive(2)).unwrap();
+        order_book.cancel(id).unwrap();
+        assert_eq!(
+            vec![OrderRemoved {
+                id,
+                reason: RemovalReason::Cancelled,
+                side: Side::Sell,
+                price: 10,
+                quantity: 3,
+            }],
+            order_book.recent_removals(1)
+        );
+        assert_eq!(Err(ReleaseError::NoSuchHold), order_book.release(token));
+        assert!(order_book.commit(token).is_err());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn prices_are_distinct_and_best_first() {
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_buy(positive(1), 10);
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_buy(positive(1), 9);
+        assert_eq!(vec![10, 9, 8], order_book.bid_prices());
+        assert!(order_book.ask_prices().is_empty());
+        order_book.unconditional_sell(positive(1), 13);
+        order_book.unconditional_sell(positive(1), 12);
+        assert_eq!(vec![12, 13], order_book.ask_prices());
+    }
+
+    #[test]
+    fn changes_after_snapshot_sequence_reproduce_the_book() {
+        use crate::api::{
+            OrderBookApi as _, ReportingOrderBookApi as _, RestWithIdOrderBookApi as _,
+        };
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(2), 8);
+        let entered = order_book.unconditional_sell(positive(3), 10);
+        let ask = *entered.as_entered_order_book().unwrap();
+        let (snapshot, sequence) = order_book.snapshot_and_sequence();
+        let changes = |order_book: &mut PriceLevelBTreeOrderBook<_, _, _>| {
+            order_book.unconditional_buy(positive(1), 10);
+            order_book.unconditional_sell(positive(4), 12);
+            order_book.cancel(ask).unwrap();
+        };
+        changes(&mut order_book);
+        assert_eq!(sequence + 3, order_book.current_sequence());
+        let mut replica = book();
+        for (side, orders) in [(Side::Buy, snapshot.buys), (Side::Sell, snapshot.sells)] {
+            for order in orders {
+                replica
+                    .rest_with_id(order.id, side, positive(order.quantity), order.unit_price)
+                    .unwrap();
+            }
+        }
+        changes(&mut replica);
+        assert!(replica.ladder_eq(&order_book));
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        use crate::api::ReportingOrderBookApi as _;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_buy(positive(2), 9);
+        order_book.unconditional_buy(positive(3), 9);
+        order_book.unconditional_sell(positive(4), 12);
+        let bytes = order_book.encode();
+        let decoded = PriceLevelBTreeOrderBook::<usize, usize, _>::decode(&bytes).unwrap();
+        assert!(decoded.logically_eq(&order_book));
+        assert_eq!(Ok(()), decoded.validate());
+    }
+
+    #[test]
+    fn decode_rejects_malformed_input() {
+        use crate::wire::DecodeError;
+
+        type Book = PriceLevelBTreeOrderBook<usize, usize, uuid::Uuid>;
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_buy(positive(2), 9);
+        let bytes = order_book.encode();
+        assert_eq!(
+            Err(DecodeError::Truncated),
+            Book::decode(&bytes[..bytes.len() - 1]).map(|_| ())
+        );
+        let trailing = [&bytes[..], &[0]].concat();
+        assert_eq!(
+            Err(DecodeError::TrailingBytes),
+            Book::decode(&trailing).map(|_| ())
+        );
+        // swap the two bid prices, which directly follow the level count and the first level
+        let mut swapped = bytes.clone();
+        let level = 8 + 8 + 16 + 8;
+        swapped[8..16].copy_from_slice(&8usize.to_le_bytes());
+        swapped[8 + level..16 + level].copy_from_slice(&9usize.to_le_bytes());
+        assert_eq!(
+            Err(DecodeError::BadOrdering),
+            Book::decode(&swapped).map(|_| ())
+        );
+
+        let mut crossed = book();
+        crossed.halt_for_auction();
+        crossed.unconditional_buy(positive(1), 9);
+        crossed.unconditional_sell(positive(1), 9);
+        assert_eq!(
+            Err(DecodeError::Crossed),
+            Book::decode(&crossed.encode()).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn top_of_book_hints_are_not_orders() {
+        use crate::api::{ReportingOrderBookApi as _, RestWithIdOrderBookApi as _};
+        use uuid::Uuid;
+
+        let mut order_book = book().with_top_of_book_hint(Some(9), Some(11));
+        assert_eq!(Some(9), order_book.highest_bid_price());
+        assert_eq!(Some(11), order_book.lowest_ask_price());
+        assert!(order_book.buys().is_empty() && order_book.sells().is_empty());
+        order_book
+            .rest_with_id(Uuid::new_v4(), Side::Buy, positive(1), 8)
+            .unwrap();
+        order_book
+            .rest_with_id(Uuid::new_v4(), Side::Sell, positive(1), 11)
+            .unwrap();
+        assert_eq!(Some(9), order_book.highest_bid_price());
+        assert_eq!(Some(11), order_book.lowest_ask_price());
+        assert_eq!(1, order_book.sells().len());
+        order_book
+            .rest_with_id(Uuid::new_v4(), Side::Buy, positive(1), 9)
+            .unwrap();
+        order_book.unconditional_buy(positive(1), 11);
+        assert_eq!(Some(9), order_book.highest_bid_price());
+        assert_eq!(None, order_book.lowest_ask_price());
+        assert_eq!(2, order_book.buys().len());
+    }
+
+    #[test]
+    fn notional_follows_fills_and_cancels() {
+        use crate::api::{OrderBookApi as _, Side};
+
+        // kept from part way through, and walked afresh, the totals agree
+        let mut order_book = book();
+        let id = *order_book
+            .unconditional_sell(positive(10), 5)
+            .as_entered_order_book()
+            .unwrap();
+        let mut order_book = order_book.with_notional_totals::<usize>();
+        order_book.unconditional_sell(positive(4), 6);
+        order_book.unconditional_buy(positive(3), 2);
+        for order_book in [
+            order_book.clone().tap_mut(|it| it.notional = None),
+            order_book.clone(),
+        ] {
+            assert_eq!(74, order_book.total_sell_notional::<usize>());
+            assert_eq!(6, order_book.total_buy_notional::<usize>());
+        }
+
+        order_book.unconditional_buy(positive(7), 5);
+        assert_eq!(15, order_book.notional_at_price::<usize>(Side::Sell, &5));
+        assert_eq!(39, order_book.total_sell_notional::<usize>());
+        assert_eq!(Ok(()), order_book.validate());
+
+        order_book.cancel(id).unwrap();
+        assert_eq!(0, order_book.notional_at_price::<usize>(Side::Sell, &5));
+        assert_eq!(24, order_book.total_sell_notional::<usize>());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn notional_totals_follow_every_change() {
+        let mut order_book = book().with_notional_totals::<usize>();
+        let bid = *order_book
+            .unconditional_buy(positive(5), 8)
+            .as_entered_order_book()
+            .unwrap();
+        let ask = *order_book
+            .unconditional_sell(positive(4), 10)
+            .as_entered_order_book()
+            .unwrap();
+        let totals = |order_book: &PriceLevelBTreeOrderBook<usize, usize, uuid::Uuid>| {
+            assert_eq!(Ok(()), order_book.validate());
+            (
+                order_book.total_buy_notional::<usize>(),
+                order_book.total_sell_notional::<usize>(),
+            )
+        };
+
+        let token = order_book.hold(bid, positive(2)).unwrap();
+        assert_eq!((3 * 8, 4 * 10), totals(&order_book));
+        order_book.release(token).unwrap();
+        assert_eq!((5 * 8, 4 * 10), totals(&order_book));
+
+        order_book.amend(bid, positive(3), 8).unwrap();
+        order_book.amend(ask, positive(6), 11).unwrap();
+        assert_eq!((3 * 8, 6 * 11), totals(&order_book));
+
+        order_book.for_each_order_at_mut(Side::Sell, &11, |_, quantity| *quantity -= 1);
+        assert_eq!((3 * 8, 5 * 11), totals(&order_book));
+
+        // the bid now crosses, and trades at the ask's price
+        order_book
+            .shift_prices(Side::Buy, super::ShiftDirection::Up, 3)
+            .unwrap();
+        assert_eq!((0, 2 * 11), totals(&order_book));
+
+        order_book.unconditional_buy(positive(1), 7);
+        order_book.trim_to_levels(0);
+        assert_eq!((0, 0), totals(&order_book));
+    }
+
+    #[test]
+    fn notional_can_be_wider_than_price() {
+        let mut order_book = PriceLevelBTreeOrderBook::<u32, u32, uuid::Uuid>::default()
+            .with_notional_totals::<u64>();
+        order_book.unconditional_buy(Positive::new(u32::MAX).unwrap(), u32::MAX);
+        order_book.unconditional_buy(Positive::new(u32::MAX).unwrap(), 1);
+        assert_eq!(
+            u64::from(u32::MAX) * u64::from(u32::MAX) + u64::from(u32::MAX),
+            order_book.total_buy_notional::<u64>()
+        );
+    }
+
+    #[test]
+    fn indicative_price_matches_resolve_crosses() {
+        use crate::api::{ReportingOrderBookApi as _, Side};
+
+        let mut order_book = PriceLevelBTreeOrderBook::<usize, usize, uuid::Uuid>::from_iter([
+            (Side::Buy, positive(5), 12),
+            (Side::Buy, positive(5), 11),
+            (Side::Buy, positive(5), 9),
+            (Side::Sell, positive(4), 10),
+            (Side::Sell, positive(8), 11),
+            (Side::Sell, positive(5), 13),
+        ]);
+        let before = order_book.snapshot();
+        assert_eq!(Some((11, 10)), order_book.indicative_price());
+        assert_eq!(before, order_book.snapshot());
+
+        let trades = order_book.resolve_crosses();
+        let volume = trades.iter().map(|trade| trade.quantity).sum::<usize>();
+        assert_eq!(10, volume);
+        assert_eq!(None, order_book.indicative_price());
+    }
+
+    #[test]
+    fn clearing_rule_breaks_volume_ties() {
+        use super::ClearingRule;
+        use crate::api::Side;
+
+        // 5 would trade at both 10 and 11, but 3 of the buys at 10 would be left over
+        let order_book = PriceLevelBTreeOrderBook::<usize, usize, uuid::Uuid>::from_iter([
+            (Side::Buy, positive(5), 11),
+            (Side::Buy, positive(3), 10),
+            (Side::Sell, positive(5), 10),
+        ]);
+        assert_eq!(Some((10, 5)), order_book.indicative_price());
+        assert_eq!(
+            Some((11, 5)),
+            order_book.indicative_price_with(ClearingRule::MaxVolumeThenMinImbalance)
+        );
+        assert_eq!(
+            Some((10, 5)),
+            order_book.indicative_price_with(ClearingRule::MaxVolumeThenReferenceClosest(9))
+        );
+        assert_eq!(
+            Some((11, 5)),
+            order_book.indicative_price_with(ClearingRule::MaxVolumeThenReferenceClosest(12))
+        );
+    }
+
+    #[test]
+    fn max_counterparties_caps_makers() {
+        use std::num::NonZeroUsize;
+
+        let mut order_book = book();
+        for _ in 0..5 {
+            order_book.unconditional_sell(positive(2), 10);
+        }
+        let ((outcome, fills), unfilled) =
+            order_book.buy_with_max_counterparties(positive(9), 10, NonZeroUsize::new(2).unwrap());
+        assert!(outcome.as_entered_order_book().is_none());
+        assert_eq!(2, fills.len());
+        assert_eq!(5, unfilled);
+        assert_eq!(6, order_book.available_to_buy(&10));
+        assert_eq!(0, order_book.available_to_sell(&0));
+
+        let (_, unfilled) =
+            order_book.buy_with_max_counterparties(positive(3), 10, NonZeroUsize::new(2).unwrap());
+        assert_eq!(0, unfilled);
+        assert_eq!(3, order_book.available_to_buy(&10));
+
+        // once the cap leaves nothing crossing, what's left rests
+        let mut order_book = book();
+        for _ in 0..2 {
+            order_book.unconditional_sell(positive(2), 10);
+        }
+        let ((outcome, fills), unfilled) =
+            order_book.buy_with_max_counterparties(positive(9), 10, NonZeroUsize::new(2).unwrap());
+        assert!(matches!(
+            outcome,
+            crate::api::BuyEntryOrExecution::PartiallyExecutedThenEntered { .. }
+        ));
+        assert_eq!(2, fills.len());
+        assert_eq!(0, unfilled);
+        assert_eq!(5, order_book.available_to_sell(&10));
+    }
+
+    #[test]
+    fn into_buys_and_sells_match_borrowing_order() {
+        use crate::api::ReportingOrderBookApi as _;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 9);
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_buy(positive(3), 9);
+        order_book.unconditional_sell(positive(4), 12);
+        order_book.unconditional_sell(positive(5), 11);
+        order_book.unconditional_sell(positive(6), 12);
+        let buys = order_book.buys();
+        let sells = order_book.sells();
+        assert_eq!(buys, order_book.clone().into_buys().collect::<Vec<_>>());
+        assert_eq!(sells, order_book.into_sells().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn max_adverse_spread_protects_makers() {
+        use super::AmendOutcome;
+        use crate::api::{BuyEntryOrExecution, SellEntryOrExecution};
+
+        let mut order_book = book().with_max_adverse_spread(5);
+        order_book.unconditional_sell(positive(10), 100);
+        order_book.unconditional_sell(positive(2), 103);
+        let buy = *order_book
+            .unconditional_buy(positive(10), 90)
+            .as_entered_order_book()
+            .unwrap();
+
+        // skips the protected sells at 100 for those at 103
+        let executed = order_book.unconditional_buy(positive(1), 106);
+        assert!(executed.as_buyer_fully_executed().is_some());
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert_eq!(10, order_book.available_to_buy(&100));
+
+        // what's left after the sells at 103 would cross those at 100, so doesn't rest
+        let (outcome, fills) = order_book.dry_run_buy(positive(2), 106);
+        assert_eq!(1, fills.len());
+        assert!(outcome.as_seller_fully_executed().is_some());
+        let executed = order_book.unconditional_buy(positive(2), 106);
+        assert_eq!(Some(&1), executed.as_seller_fully_executed().map(|it| it.2));
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert_eq!(2, order_book.order_count());
+
+        // nothing left to trade with short of the protected sells
+        assert_eq!(
+            BuyEntryOrExecution::Rejected,
+            order_book.unconditional_buy(positive(1), 106)
+        );
+        assert_eq!(
+            SellEntryOrExecution::Rejected,
+            order_book.unconditional_sell(positive(1), 80)
+        );
+        assert_eq!(
+            Ok(AmendOutcome::Cancelled { fills: vec![] }),
+            order_book.amend(buy, positive(10), 106)
+        );
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert_eq!(1, order_book.order_count());
+
+        let executed = order_book.unconditional_buy(positive(1), 105);
+        assert!(executed.as_buyer_fully_executed().is_some());
+        assert_eq!(9, order_book.available_to_buy(&100));
+    }
+
+    #[test]
+    fn order_count_follows_fills() {
+        let mut order_book = book();
+        assert!(order_book.is_empty());
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_buy(positive(1), 9);
+        assert_eq!(3, order_book.order_count());
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(3, order_book.order_count());
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(2, order_book.order_count());
+        // the remainder rests
+        order_book.unconditional_sell(positive(5), 9);
+        assert_eq!(2, order_book.order_count());
+        order_book.unconditional_buy(positive(6), 10);
+        assert!(order_book.is_empty());
+    }
+
+    #[test]
+    fn halted_book_rejects_orders() {
+        use super::BookStatus;
+        use crate::api::{BuyEntryOrExecution, ExecutionClass};
+
+        let mut order_book = book().with_completed_capacity(1);
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.halt();
+        assert_eq!(BookStatus::Halted, order_book.status());
+        let sequence = order_book.current_sequence();
+        assert_eq!(
+            BuyEntryOrExecution::Rejected,
+            order_book.unconditional_buy(positive(1), 10)
+        );
+        assert_eq!(
+            ExecutionClass::Rejected,
+            order_book.unconditional_sell(positive(1), 9).classify()
+        );
+        let (outcome, fills) = order_book.dry_run_buy(positive(1), 10);
+        assert_eq!((BuyEntryOrExecution::Rejected, vec![]), (outcome, fills));
+        assert_eq!(None, order_book.buy_reporting_rest(positive(1), 10).1);
+        assert_eq!(sequence, order_book.current_sequence());
+        assert_eq!(1, order_book.order_count());
+        assert!(order_book.resume().is_empty());
+        assert_eq!(1, order_book.available_to_buy(&10));
+    }
+
+    #[test]
+    fn auction_rests_orders_until_resumed() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.halt_for_auction();
+        let (outcome, _) = order_book.dry_run_buy(positive(2), 11);
+        assert!(outcome.as_entered_order_book().is_some());
+        let entered = order_book.unconditional_buy(positive(2), 11);
+        assert!(entered.as_entered_order_book().is_some());
+        assert_eq!(BookState::Crossed, order_book.book_state());
+        let trades = order_book.resume();
+        assert_eq!(1, trades.len());
+        assert_eq!(10, trades[0].unit_price);
+        assert_eq!(1, order_book.available_to_sell(&11));
+    }
+
+    #[test]
+    fn rest_report_has_best_opposing_price() {
+        use super::RestReport;
+
+        let mut order_book = book();
+        let (_, report) = order_book.buy_reporting_rest(positive(1), 10);
+        assert_eq!(
+            Some(RestReport {
+                best_opposing: None
+            }),
+            report
+        );
+        let (_, report) = order_book.sell_reporting_rest(positive(1), 12);
+        assert_eq!(
+            Some(RestReport {
+                best_opposing: Some(10)
+            }),
+            report
+        );
+        let (_, report) = order_book.buy_reporting_rest(positive(1), 12);
+        assert_eq!(None, report);
+    }
+
+    #[test]
+    fn fold_orders_visits_every_order() {
+        use crate::api::Side;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 9);
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_sell(positive(4), 12);
+        order_book.unconditional_sell(positive(8), 11);
+        let total = order_book.fold_orders(0, |total, _, order| total + order.quantity);
+        assert_eq!(
+            order_book.available_to_sell(&0) + order_book.available_to_buy(&usize::MAX),
+            total
+        );
+        let visited = order_book.fold_orders(vec![], |visited, side, order| {
+            visited.tap_mut(|it| it.push((side, order.unit_price)))
+        });
+        assert_eq!(
+            vec![
+                (Side::Buy, 10),
+                (Side::Buy, 9),
+                (Side::Sell, 11),
+                (Side::Sell, 12)
+            ],
+            visited
+        );
+    }
+
+    #[test]
+    fn touch_matches_accessors() {
+        use crate::api::Side;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(10), 9);
+        assert_eq!(None, order_book.touch());
+        order_book.unconditional_buy(positive(20), 9);
+        order_book.unconditional_buy(positive(5), 8);
+        order_book.unconditional_sell(positive(7), 11);
+        let touch = order_book.touch().unwrap();
+        assert_eq!(order_book.highest_bid_price(), Some(touch.bid_price));
+        assert_eq!(order_book.lowest_ask_price(), Some(touch.ask_price));
+        assert_eq!(vec![(9, touch.bid_quantity)], order_book.depth(1).bids);
+        assert_eq!(vec![(11, touch.ask_quantity)], order_book.depth(1).asks);
+        assert_eq!(
+            touch.bid_orders,
+            order_book.level_queue(Side::Buy, &9).unwrap().len()
+        );
+        assert_eq!(1, touch.ask_orders);
+    }
+
+    #[test]
+    fn min_quantity_rejects_small_executions() {
+        use super::MinimumQuantityNotMet;
+        use crate::api::ReportingOrderBookApi as _;
+
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(40), 10);
+        let before = order_book.snapshot();
+        assert_eq!(
+            Err(MinimumQuantityNotMet),
+            order_book.buy_with_min_quantity(positive(100), 10, 50)
+        );
+        assert_eq!(before, order_book.snapshot());
+        let (_, fills) = order_book
+            .buy_with_min_quantity(positive(100), 10, 40)
+            .unwrap();
+        assert_eq!(40, fills[0].quantity);
+        let (outcome, _) = order_book
+            .sell_with_min_quantity(positive(10), 12, 50)
+            .unwrap();
+        assert!(outcome.as_entered_order_book().is_some());
+    }
+
+    #[test]
+    fn resting_min_quantity_is_passed_over() {
+        let mut order_book = book();
+        let (outcome, _) = order_book
+            .sell_with_min_quantity(positive(100), 10, 50)
+            .unwrap();
+        let maker = *outcome.as_entered_order_book().unwrap();
+        let behind = *order_book
+            .unconditional_sell(positive(30), 10)
+            .as_entered_order_book()
+            .unwrap();
+        // too little for `maker`, which keeps its place
+        let (fills, discarded) = order_book.buy_ioc(positive(40), 10);
+        assert_eq!(
+            vec![(behind, 30)],
+            fills
+                .iter()
+                .map(|fill| (fill.maker, fill.quantity))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(10, discarded);
+        assert_eq!(
+            Some(vec![(maker, 100)]),
+            order_book.level_queue(Side::Sell, &10)
+        );
+        let (fills, _) = order_book.buy_ioc(positive(60), 10);
+        assert_eq!((maker, 60), (fills[0].maker, fills[0].quantity));
+        // less than the minimum is left, so only all of it will do
+        let (fills, _) = order_book.buy_ioc(positive(20), 10);
+        assert!(fills.is_empty());
+        let (fills, _) = order_book.buy_ioc(positive(40), 10);
+        assert_eq!((maker, 40), (fills[0].maker, fills[0].quantity));
+        assert!(order_book.is_empty());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn cancel_if_not_best() {
+        use super::CancelOutcome;
+
+        let mut order_book = book();
+        let [worse, best] = [8, 10].map(|price| {
+            let entered = order_book.unconditional_buy(positive(1), price);
+            *entered.as_entered_order_book().unwrap()
+        });
+        let best_bid = order_book.highest_bid_price();
+        let not_best = |order: &crate::api::Order<_, _, _>| Some(order.unit_price) != best_bid;
+        assert_eq!(
+            Ok(CancelOutcome::Kept),
+            order_book.cancel_if(best, not_best)
+        );
+        assert_eq!(
+            Ok(CancelOutcome::Cancelled),
+            order_book.cancel_if(worse, not_best)
+        );
+        assert_eq!(vec![10], order_book.bid_prices());
+        assert!(order_book.cancel_if(worse, not_best).is_err());
+    }
+
+    #[test]
+    fn amend_keeps_priority_only_when_reducing_quantity() {
+        use super::AmendOutcome;
+
+        let mut order_book = book();
+        let [first, second] = [1, 2].map(|quantity| {
+            let entered = order_book.unconditional_buy(positive(quantity + 2), 10);
+            *entered.as_entered_order_book().unwrap()
+        });
+        let front = |order_book: &PriceLevelBTreeOrderBook<_, _, _>| {
+            order_book.level_queue(Side::Buy, &10).unwrap()[0]
+        };
+
+        assert_eq!(
+            Ok(AmendOutcome::KeptPriority),
+            order_book.amend(first, positive(2), 10)
+        );
+        assert_eq!((first, 2), front(&order_book));
+
+        assert_eq!(
+            Ok(AmendOutcome::LostPriority),
+            order_book.amend(first, positive(5), 10)
+        );
+        assert_eq!((second, 4), front(&order_book));
+
+        assert_eq!(
+            Ok(AmendOutcome::LostPriority),
+            order_book.amend(second, positive(4), 9)
+        );
+        assert_eq!(Some((9, 4)), order_book.depth(2).bids.get(1).copied());
+
+        assert_eq!(
+            Ok(AmendOutcome::LostPriority),
+            order_book.amend(second, positive(1), 10)
+        );
+        assert_eq!(
+            vec![(first, 5), (second, 1)],
+            order_book.level_queue(Side::Buy, &10).unwrap()
+        );
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn amend_through_the_book_trades() {
+        use super::AmendOutcome;
+        use crate::api::OrderBookApi as _;
+
+        let mut order_book = book();
+        let buy = *order_book
+            .unconditional_buy(positive(10), 8)
+            .as_entered_order_book()
+            .unwrap();
+        order_book.unconditional_sell(positive(3), 9);
+        order_book.unconditional_sell(positive(4), 10);
+        order_book.unconditional_sell(positive(5), 12);
+        let Ok(AmendOutcome::Executed { fills }) = order_book.amend(buy, positive(10), 11) else {
+            panic!("amend should have traded")
+        };
+        assert_eq!(
+            vec![(9, 3), (10, 4)],
+            fills
+                .iter()
+                .map(|fill| (fill.unit_price, fill.quantity))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Ok(crate::api::BuyOrSell::Buy {
+                quantity: 3,
+                unit_price: 11
+            }),
+            order_book.query(buy)
+        );
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert!(order_book
+            .amend(uuid::Uuid::new_v4(), positive(1), 1)
+            .is_err());
+
+        order_book.halt();
+        assert_eq!(
+            Ok(AmendOutcome::Rejected),
+            order_book.amend(buy, positive(3), 12)
+        );
+        assert_eq!(BookState::Normal, order_book.book_state());
+        assert_eq!(
+            Ok(AmendOutcome::LostPriority),
+            order_book.amend(buy, positive(4), 10)
+        );
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn next_in_queue_is_the_order_behind() {
+        let mut order_book = book();
+        let [first, second, third] = [1, 2, 3].map(|quantity| {
+            let entered = order_book.unconditional_sell(positive(quantity), 10);
+            *entered.as_entered_order_book().unwrap()
+        });
+        assert_eq!(Ok(Some(second)), order_book.next_in_queue(first));
+        assert_eq!(Ok(Some(third)), order_book.next_in_queue(second));
+        assert_eq!(Ok(None), order_book.next_in_queue(third));
+        assert!(order_book.next_in_queue(uuid::Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn checked_totals_signal_overflow() {
+        let mut order_book = [(Side::Sell, Positive::new(200u8).unwrap(), 10)]
+            .into_iter()
+            .collect::<PriceLevelBTreeOrderBook<_, usize, _>>();
+        order_book.extend([(Side::Sell, Positive::new(50).unwrap(), 11)]);
+        assert_eq!(Some(250), order_book.checked_available_to_buy(&11));
+        order_book.extend([(Side::Sell, Positive::new(100).unwrap(), 10)]);
+        assert_eq!(None, order_book.checked_available_to_buy(&11));
+        assert_eq!(None, order_book.checked_depth(1));
+        assert_eq!(Some(0), order_book.checked_available_to_sell(&0));
+    }
+
+    #[test]
+    fn trim_to_levels_evicts_the_worst_levels() {
+        let mut order_book = [8, 9, 10, 11, 12]
+            .map(|price| (Side::Sell, positive(price - 7), price))
+            .into_iter()
+            .chain([(Side::Buy, positive(1), 5)])
+            .collect::<PriceLevelBTreeOrderBook<_, _, _>>();
+        let evicted = order_book
+            .trim_to_levels(3)
+            .into_iter()
+            .map(|order| (order.unit_price, order.quantity))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(11, 4), (12, 5)], evicted);
+        assert_eq!(vec![8, 9, 10], order_book.ask_prices());
+        assert_eq!(vec![5], order_book.bid_prices());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn largest_orders_ignores_price() {
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_buy(positive(9), 7);
+        order_book.unconditional_buy(positive(5), 8);
+        order_book.unconditional_buy(positive(5), 9);
+        order_book.unconditional_sell(positive(20), 12);
+        let largest = order_book
+            .largest_orders(Side::Buy, 3)
+            .into_iter()
+            .map(|order| (order.unit_price, order.quantity))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(7, 9), (9, 5), (8, 5)], largest);
+        assert_eq!(1, order_book.largest_orders(Side::Sell, 3).len());
+    }
+
+    #[test]
+    fn with_capacity_behaves_like_default() {
+        let mut presized = PriceLevelBTreeOrderBook::with_capacity(16);
+        let mut order_book = book();
+        for it in [&mut presized, &mut order_book] {
+            it.unconditional_buy(positive(1), 8);
+            it.unconditional_buy(positive(2), 9);
+            it.unconditional_sell(positive(3), 11);
+            it.unconditional_sell(positive(1), 9);
+        }
+        assert_eq!(order_book.depth(5), presized.depth(5));
+        assert_eq!(Ok(()), presized.validate());
+    }
+
+    #[test]
+    fn collect_rests_orders_in_priority_order() {
+        use crate::api::ReportingOrderBookApi as _;
+
+        let mut order_book = [
+            (Side::Buy, positive(1), 8),
+            (Side::Sell, positive(2), 12),
+            (Side::Buy, positive(3), 9),
+            (Side::Buy, positive(4), 8),
+            (Side::Sell, positive(5), 11),
+        ]
+        .into_iter()
+        .collect::<PriceLevelBTreeOrderBook<_, _, _>>();
+        order_book.extend([(Side::Sell, positive(6), 11)]);
+        let levels = |orders: Vec<crate::api::Order<_, _, _>>| {
+            orders
+                .into_iter()
+                .map(|order| (order.unit_price, order.quantity))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(9, 3), (8, 1), (8, 4)], levels(order_book.buys()));
+        assert_eq!(vec![(11, 5), (11, 6), (12, 2)], levels(order_book.sells()));
+        assert_eq!(6, order_book.current_sequence());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn cancel_reports_whether_level_is_now_empty() {
+        use super::CancelReport;
+
+        let mut order_book = book();
+        let [first, second] = [1, 2].map(|quantity| {
+            let entered = order_book.unconditional_sell(positive(quantity), 10);
+            *entered.as_entered_order_book().unwrap()
+        });
+        assert_eq!(
+            Ok(CancelReport {
+                level_now_empty: false
+            }),
+            order_book.cancel_reporting_level(first)
+        );
+        assert_eq!(
+            Ok(CancelReport {
+                level_now_empty: true
+            }),
+            order_book.cancel_reporting_level(second)
+        );
+        assert!(order_book.level_queue(Side::Sell, &10).is_none());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn condition_may_inspect_the_book() {
+        use std::ops::ControlFlow;
+
+        let mut order_book = book();
+        order_book.unconditional_buy(positive(1), 8);
+        order_book.unconditional_buy(positive(1), 10);
+        let mut seen = None;
+        let aborted = order_book.conditional_sell_inspecting_book(positive(2), 8, |book, args| {
+            seen = Some((*args.buyer_id, book.highest_bid_price()));
+            match book.quantity_to_move_bid_to(&8) {
+                0 => ControlFlow::Continue(()),
+                _ => ControlFlow::Break("would move the bid"),
+            }
+        });
+        assert_eq!(Err("would move the bid"), aborted);
+        let best_bid = order_book.level_queue(Side::Buy, &10).unwrap()[0].0;
+        assert_eq!(Some((best_bid, Some(10))), seen);
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[test]
+    fn checking_fills_aborts_on_third_counterparty() {
+        use crate::api::ReportingOrderBookApi as _;
+        use std::ops::ControlFlow;
+
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_sell(positive(3), 10);
+        order_book.unconditional_sell(positive(4), 10);
+        let before = order_book.snapshot();
+        let mut counterparties = 0;
+        let mut notional = 0;
+        // the walk stops at the third maker, rather than going on to the fourth
+        let aborted = order_book.conditional_buy_checking_fills(positive(10), 10, |fill| {
+            counterparties += 1;
+            notional += fill.unit_price * fill.quantity;
+            match notional > 30 {
+                true => ControlFlow::Break(notional),
+                false => ControlFlow::Continue(()),
+            }
+        });
+        assert_eq!(Err(60), aborted);
+        assert_eq!(3, counterparties);
+        assert_eq!(before, order_book.snapshot());
+        let (_, fills) = order_book
+            .conditional_buy_checking_fills(positive(3), 10, |_| ControlFlow::<()>::Continue(()))
+            .unwrap();
+        assert_eq!(2, fills.len());
+        assert_eq!(Ok(()), order_book.validate());
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    #[cfg(feature = "serde")]
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn trade_log_has_a_line_per_trade() {
+        use super::TradeLogErrorPolicy;
+
+        let buffer = SharedBuffer::default();
+        let mut order_book = book().with_trade_log(buffer.clone(), TradeLogErrorPolicy::Panic);
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(1), 11);
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_buy(positive(1), 11);
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let trades = log
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<crate::api::Trade<usize, usize, uuid::Uuid>>(line).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(3, trades.len());
+        assert_eq!(11, trades[2].unit_price);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn trade_log_failure_is_kept() {
+        use super::TradeLogErrorPolicy;
+
+        struct Broken;
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut order_book = book().with_trade_log(Broken, TradeLogErrorPolicy::Stop);
+        order_book.unconditional_sell(positive(1), 10);
+        assert!(order_book.trade_log_error().is_none());
+        order_book.unconditional_buy(positive(1), 10);
+        assert_eq!(
+            std::io::ErrorKind::BrokenPipe,
+            order_book.trade_log_error().unwrap().kind()
+        );
+        assert_eq!(0, order_book.available_to_buy(&10));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn to_json_value_is_a_ladder() {
+        let mut order_book = book();
+        let entered = order_book.unconditional_buy(positive(2), 10);
+        let buy = *entered.as_entered_order_book().unwrap();
+        let entered = order_book.unconditional_sell(positive(2), 12);
+        let sell = *entered.as_entered_order_book().unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "bids": [{ "price": 10, "quantity": 2, "id": buy.to_string() }],
+                "asks": [{ "price": 12, "quantity": 2, "id": sell.to_string() }],
+            }),
+            order_book.to_json_value()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_preserves_time_priority() {
+        let mut order_book = book();
+        for quantity in [3, 1, 2] {
+            order_book.unconditional_buy(positive(quantity), 10);
+        }
+        let json = serde_json::to_string(&order_book).unwrap();
+        let round_tripped =
+            serde_json::from_str::<PriceLevelBTreeOrderBook<usize, usize, uuid::Uuid>>(&json)
+                .unwrap();
+        assert_eq!(
+            order_book.level_queue(Side::Buy, &10),
+            round_tripped.level_queue(Side::Buy, &10)
+        );
+        assert_eq!(Ok(()), round_tripped.validate());
+        assert!(serde_json::from_str::<NonEmpty<std::collections::VecDeque<usize>>>("[]").is_err());
+    }
+
+    #[test]
+    fn liquidity_within_ticks_widens_monotonically() {
+        let mut order_book = book();
+        assert_eq!(0, order_book.liquidity_within_ticks(Side::Buy, 3, 2));
+        for (quantity, price) in [(1, 10), (2, 8), (4, 6), (8, 1)] {
+            order_book.unconditional_buy(positive(quantity), price);
+        }
+        for (quantity, price) in [(1, 12), (2, 13), (4, 16)] {
+            order_book.unconditional_sell(positive(quantity), price);
+        }
+        let bids = (0..8)
+            .map(|ticks| order_book.liquidity_within_ticks(Side::Buy, ticks, 2))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 3, 7, 7, 7, 15, 15, 15], bids);
+        let asks = (0..4)
+            .map(|ticks| order_book.liquidity_within_ticks(Side::Sell, ticks, 2))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 3, 7, 7], asks);
+        assert_eq!(
+            7,
+            order_book.liquidity_within_ticks(Side::Sell, usize::MAX, 2)
+        );
+        assert_eq!(
+            15,
+            order_book.liquidity_within_ticks(Side::Buy, usize::MAX, 2)
+        );
+
+        let mut order_book = PriceLevelBTreeOrderBook::<usize, i64, uuid::Uuid>::default();
+        for (quantity, price) in [(1, -2), (2, -4), (4, -9)] {
+            order_book.unconditional_buy(positive(quantity), price);
+        }
+        for (quantity, price) in [(1, -1), (2, 1)] {
+            order_book.unconditional_sell(positive(quantity), price);
+        }
+        let bids = (0..5)
+            .map(|ticks| order_book.liquidity_within_ticks(Side::Buy, ticks, 2))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 3, 3, 3, 7], bids);
+        assert_eq!(3, order_book.liquidity_within_ticks(Side::Sell, 1, 2));
+        assert_eq!(
+            7,
+            order_book.liquidity_within_ticks(Side::Buy, usize::MAX, 2)
+        );
+    }
+
+    #[test]
+    fn levels_in_range_skips_missing_prices() {
+        let mut order_book = book();
+        order_book.unconditional_sell(positive(1), 10);
+        order_book.unconditional_sell(positive(2), 10);
+        order_book.unconditional_sell(positive(4), 12);
+        order_book.unconditional_sell(positive(8), 14);
+        assert_eq!(
+            vec![(10, 3), (12, 4)],
+            order_book.levels_in_range(Side::Sell, 9..=13)
+        );
+        assert_eq!(
+            Vec::<(usize, usize)>::new(),
+            order_book.levels_in_range(Side::Buy, 9..=13)
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let inverted = 13..=9;
+        assert_eq!(
+            Vec::<(usize, usize)>::new(),
+            order_book.levels_in_range(Side::Sell, inverted)
+        );
+    }
+
+    #[test]
+    fn price_bounds_span_every_level() {
+        let mut order_book = book();
+        assert_eq!(None, order_book.lowest_bid_price());
+        assert_eq!(None, order_book.highest_ask_price());
+        for price in [3, 1, 2] {
+            order_book.unconditional_buy(positive(1), price);
+        }
+        for price in [5, 6, 4] {
+            order_book.unconditional_sell(positive(1), price);
+        }
+        assert_eq!(Some(1), order_book.lowest_bid_price());
+        assert_eq!(Some(3), order_book.highest_bid_price());
+        assert_eq!(Some(4), order_book.lowest_ask_price());
+        assert_eq!(Some(6), order_book.highest_ask_price());
+    }
+
+    #[test]
+    fn average_prices_are_weighted_by_quantity() {
+        let mut order_book = book();
+        assert_eq!(None, order_book.avg_bid_price());
+        order_book.unconditional_buy(positive(1), 10);
+        order_book.unconditional_buy(positive(4), 20);
+        order_book.unconditional_sell(positive(4), 30);
+        order_book.unconditional_sell(positive(1), 40);
+        assert_eq!(Some(18), order_book.avg_bid_price());
+        assert_eq!(Some(32), order_book.avg_ask_price());
+    }
+
+    #[test]
+    fn book_state_compares_best_bid_and_ask() {
+        let mut order_book = book();
+        assert_eq!(BookState::Normal, order_book.book_state());
+        order_book.rest(Side::Buy, 1, 10);
+        assert_eq!(BookState::Normal, order_book.book_state());
+        order_book.rest(Side::Sell, 1, 11);
+        assert_eq!(BookState::Normal, order_book.book_state());
+        order_book.rest(Side::Sell, 1, 10);
+        assert_eq!(BookState::Locked, order_book.book_state());
+        order_book.rest(Side::Sell, 1, 9);
+        assert_eq!(BookState::Crossed, order_book.book_state());
+    }
+
+    #[test]
+    fn can_be_driven_as_a_trait_object() {
+        use crate::api::{BuyEntryOrExecution, DynOrderBook, SellEntryOrExecution};
+        use uuid::Uuid;
+
+        let mut order_book: Box<dyn DynOrderBook<usize, usize, Uuid>> = Box::new(book());
+        let BuyEntryOrExecution::EnteredOrderBook { id } =
+            order_book.unconditional_buy(positive(2), 10)
+        else {
+            panic!("buy should have entered the book")
+        };
+        assert_eq!(
+            SellEntryOrExecution::SellerFullyExecuted {
+                buyer: id,
+                spread: None,
+                buyers_remaining: 1
+            },
+            order_book.unconditional_sell(positive(1), 10)
+        );
+        assert_eq!(1, order_book.buys().len());
+        assert!(order_book.sells().is_empty());
+        assert!(order_book.query(id).is_ok());
+        assert!(order_book.cancel(id).is_ok());
+        assert!(order_book.buys().is_empty());
+    }
+
+    #[test]
+    fn quote_agrees_with_accessors() {
+        use crate::api::{DepthSnapshot, Quote};
+
+        let mut order_book = book();
+        assert_eq!(
+            Quote {
+                bids: vec![],
+                asks: vec![],
+                spread: None,
+                mid_price: None
+            },
+            order_book.quote(2)
+        );
+        order_book.unconditional_buy(positive(1), 10);
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_buy(positive(4), 8);
+        order_book.unconditional_buy(positive(8), 6);
+        order_book.unconditional_sell(positive(3), 15);
+        assert_eq!(
+            DepthSnapshot {
+                bids: vec![(10, 3), (8, 4)],
+                asks: vec![(15, 3)],
+            },
+            order_book.depth(2)
+        );
+        assert_eq!(Some(5), order_book.spread());
+        assert_eq!(Some(12), order_book.mid_price());
+        let DepthSnapshot { bids, asks } = order_book.depth(2);
+        assert_eq!(
+            Quote {
+                bids,
+                asks,
+                spread: order_book.spread(),
+                mid_price: order_book.mid_price(),
+            },
+            order_book.quote(2)
+        );
+    }
+
+    #[test]
+    fn crossed_book_has_no_spread() {
+        let mut order_book = book();
+        order_book.halt_for_auction();
+        order_book.unconditional_buy(positive(1), 12);
+        order_book.unconditional_sell(positive(1), 10);
+        assert_eq!(BookState::Crossed, order_book.book_state());
+        assert_eq!(None, order_book.spread());
+        assert_eq!(None, order_book.mid_price());
+        assert_eq!(None, order_book.quote(1).spread);
+    }
+
+    #[test]
+    fn checksum_depends_only_on_levels() {
+        let mut left = book();
+        left.unconditional_buy(positive(1), 10);
+        left.unconditional_buy(positive(2), 10);
+        left.unconditional_buy(positive(4), 9);
+        left.unconditional_sell(positive(3), 12);
+        let mut right = book();
+        right.unconditional_sell(positive(3), 12);
+        right.unconditional_buy(positive(4), 9);
+        right.unconditional_buy(positive(3), 10);
+        assert_eq!(left.checksum(5), right.checksum(5));
+        right.unconditional_buy(positive(1), 9);
+        assert_ne!(left.checksum(5), right.checksum(5));
+        assert_eq!(left.checksum(1), right.checksum(1));
+    }
+
+    #[test]
+    fn checksum_has_a_fixed_layout() {
+        let order_book = PriceLevelBTreeOrderBook::<usize, usize, uuid::Uuid>::from_iter([
+            (Side::Buy, positive(4), 9),
+            (Side::Buy, positive(3), 10),
+            (Side::Sell, positive(3), 12),
+        ]);
+        assert_eq!(0xc1b4_dca1_d196_d64d, order_book.checksum(5));
+    }
+
+    #[test]
+    fn query_ref_borrows_resting_order() {
+        use crate::api::{BuyEntryOrExecution, BuyOrSell};
+
+        let mut order_book = book();
+        let BuyEntryOrExecution::EnteredOrderBook { id } =
+            order_book.unconditional_buy(positive(2), 10)
+        else {
+            panic!("buy should have entered the book")
+        };
+        assert_eq!(
+            BuyOrSell::Buy {
+                quantity: &2,
+                unit_price: &10
+            },
+            order_book.query_ref(id).unwrap()
+        );
+    }
+
+    #[test]
+    fn query_status_remembers_completed_orders_until_evicted() {
+        use crate::api::{BuyEntryOrExecution, OrderBookApi as _};
+
+        let mut order_book = book().with_completed_capacity(1);
+        let BuyEntryOrExecution::EnteredOrderBook { id: filled } =
+            order_book.unconditional_buy(positive(1), 10)
+        else {
+            panic!("buy should have entered the book")
+        };
+        let BuyEntryOrExecution::EnteredOrderBook { id: cancelled } =
+            order_book.unconditional_buy(positive(1), 9)
+        else {
+            panic!("buy should have entered the book")
+        };
+        assert_eq!(OrderStatus::Resting, order_book.query_status(&filled));
+        order_book.unconditional_sell(positive(1), 10);
+        assert_eq!(OrderStatus::Filled, order_book.query_status(&filled));
+        order_book.cancel(cancelled).unwrap();
+        assert_eq!(OrderStatus::Cancelled, order_book.query_status(&cancelled));
+        assert_eq!(OrderStatus::Unknown, order_book.query_status(&filled));
+    }
+
+    #[test]
+    fn fills_of_reports_every_maker_swept() {
+        use crate::api::OrderBookApi as _;
+
+        let mut order_book = book().with_completed_capacity(1);
+        let makers = [(2, 10), (3, 11)].map(|(quantity, price)| {
+            let entered = order_book.unconditional_sell(positive(quantity), price);
+            *entered.as_entered_order_book().unwrap()
+        });
+        let entered = order_book.unconditional_buy(positive(6), 11);
+        let (&taker, ..) = entered.as_partially_executed_then_entered().unwrap();
+        let swept = Some(vec![(makers[0], 2), (makers[1], 3)]);
+        assert_eq!(swept, order_book.fills_of(&taker));
+
+        // still remembered once the rest of the taker is filled
+        order_book.unconditional_sell(positive(1), 11);
+        assert_eq!(OrderStatus::Filled, order_book.query_status(&taker));
+        assert_eq!(swept, order_book.fills_of(&taker));
+
+        let entered = order_book.unconditional_buy(positive(1), 9);
+        let id = *entered.as_entered_order_book().unwrap();
+        assert_eq!(Some(vec![]), order_book.fills_of(&id));
+        order_book.cancel(id).unwrap();
+        assert_eq!(None, order_book.fills_of(&taker));
+    }
+
+    #[test]
+    fn match_against_leaves_uncrossed_levels_untouched() {
+        use super::match_against;
+        use std::{collections::BTreeMap, ops::ControlFlow};
+
+        let mut bids = BTreeMap::from([(10, NonEmpty::vecdeque(('a', 1)))]);
+        let before = bids.clone();
+        let matched = match_against(&mut bids, Side::Sell, 1usize, &11, |_| {
+            ControlFlow::<()>::Continue(())
+        });
+        assert!(matches!(matched, Ok(None)));
+        assert_eq!(before, bids);
+    }
+
+    #[test]
+    fn match_against_reinserts_partially_executed_maker() {
+        use super::{match_against, MatchOutcome};
+        use std::{collections::BTreeMap, ops::ControlFlow};
+
+        let mut bids = BTreeMap::from([
+            (9, NonEmpty::vecdeque(('c', 1))),
+            (
+                10,
+                NonEmpty::vecdeque(('a', 1)).tap_mut(|it| it.push_back(('b', 3))),
+            ),
+        ]);
+        let (outcome, fills) = match_against(&mut bids, Side::Sell, 2usize, &8, |maker| {
+            assert_eq!(&'a', maker);
+            ControlFlow::<()>::Continue(())
+        })
+        .unwrap()
+        .unwrap();
+        assert!(matches!(
+            outcome,
+            MatchOutcome::TakerFullyExecuted {
+                maker: 'b',
+                makers_remaining: 2,
+                ..
+            }
+        ));
+        assert_eq!(2, fills.len());
+        assert_eq!(
+            BTreeMap::from([
+                (9, NonEmpty::vecdeque(('c', 1))),
+                (10, NonEmpty::vecdeque(('b', 2))),
+            ]),
+            bids
+        );
+    }
+
+    #[test]
+    fn stale_index_is_logged_and_recovered_from() {
+        use crate::api::{BuyEntryOrExecution, NoSuchOrder, OrderBookApi as _};
+        use std::{
+            io,
+            sync::{Arc, Mutex},
+        };
+
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<u8>>>);
+        impl io::Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut order_book = book().with_stale_index_policy(StaleIndexPolicy::Recover);
+        let BuyEntryOrExecution::EnteredOrderBook { id } =
+            order_book.unconditional_buy(positive(1), 10)
+        else {
+            panic!("buy should have entered the book")
+        };
+        order_book.buys.clear();
+
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let capture = capture.clone();
+                move || capture.clone()
+            })
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(Err(NoSuchOrder), order_book.query_ref(id));
+            assert_eq!(Err(NoSuchOrder), order_book.cancel(id));
+        });
+        let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            2,
+            logs.lines()
+                .filter(|line| line.contains("ERROR") && line.contains(&id.to_string()))
+                .count(),
+            "{logs}"
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn random_operations_preserve_invariants(ops in crate::property_suite::ops()) {
+            crate::property_suite::run(
+                &mut PriceLevelBTreeOrderBook::default().with_notional_totals::<usize>(),
+                ops,
+                |order_book| order_book.validate().unwrap(),
+            );
+        }
+
+        #[test]
+        fn dry_run_agrees_with_trading(
+            ops in crate::property_suite::ops(),
+            quantity in 1..=10usize,
+            unit_price in 1..=10usize,
+        ) {
+            use crate::api::FillReportingOrderBookApi as _;
+            use std::{mem::discriminant, ops::ControlFlow};
+
+            let mut order_book = book().with_max_adverse_spread(3);
+            crate::property_suite::run(&mut order_book, ops, |_| {});
+            let (dry_outcome, dry_fills) = order_book.dry_run_sell(positive(quantity), unit_price);
+            let (outcome, fills) = order_book
+                .conditional_sell_reporting_fills(positive(quantity), unit_price, |_| {
+                    ControlFlow::<()>::Continue(())
+                })
+                .unwrap();
+            // a new order's id is only decided when it rests
+            proptest::prop_assert_eq!(discriminant(&dry_outcome), discriminant(&outcome));
+            proptest::prop_assert_eq!(dry_fills, fills);
+        }
+    }
 }
@@ -0,0 +1,323 @@
+use pretty_assertions::assert_eq;
+use proptest::{prelude::*, sample::Index};
+use std::{collections::HashMap, fmt::Debug, hash::Hash, ops::ControlFlow};
+
+use crate::api::{
+    BuyEntryOrExecution, FillReport, FillReportingOrderBookApi, Order, ReportingOrderBookApi,
+    SellEntryOrExecution, UnconditionalOrderBookApi,
+};
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    Buy {
+        quantity: usize,
+        unit_price: usize,
+    },
+    Sell {
+        quantity: usize,
+        unit_price: usize,
+    },
+    /// Cancel an order which has previously entered the book (it may since have been executed)
+    Cancel(Index),
+}
+
+/// Prices are drawn from a narrow band so that orders frequently trade
+pub fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (1..=5usize, 1..=10usize).prop_map(|(quantity, unit_price)| Op::Buy {
+            quantity,
+            unit_price
+        }),
+        (1..=5usize, 1..=10usize).prop_map(|(quantity, unit_price)| Op::Sell {
+            quantity,
+            unit_price
+        }),
+        any::<Index>().prop_map(Op::Cancel),
+    ]
+}
+
+pub fn ops() -> impl Strategy<Value = Vec<Op>> {
+    proptest::collection::vec(op(), 0..64)
+}
+
+/// Apply each of `ops` in turn, checking that the book is uncrossed and calling `validate` after each.
+pub fn run<T, OrderIdT>(order_book: &mut T, ops: Vec<Op>, validate: impl Fn(&T))
+where
+    T: ReportingOrderBookApi<usize, usize, OrderIdT>,
+    OrderIdT: Clone + Debug,
+{
+    let mut entered = Vec::new();
+    for op in ops {
+        match op {
+            Op::Buy {
+                quantity,
+                unit_price,
+            } => {
+                if let BuyEntryOrExecution::EnteredOrderBook { id }
+                | BuyEntryOrExecution::PartiallyExecutedThenEntered { id, .. } = order_book
+                    .unconditional_buy(numwit::Positive::new(quantity).unwrap(), unit_price)
+                {
+                    entered.push(id)
+                }
+            }
+            Op::Sell {
+                quantity,
+                unit_price,
+            } => {
+                if let SellEntryOrExecution::EnteredOrderBook { id }
+                | SellEntryOrExecution::PartiallyExecutedThenEntered { id, .. } = order_book
+                    .unconditional_sell(numwit::Positive::new(quantity).unwrap(), unit_price)
+                {
+                    entered.push(id)
+                }
+            }
+            Op::Cancel(index) => {
+                if !entered.is_empty() {
+                    let id = entered.swap_remove(index.index(entered.len()));
+                    let _ = order_book.cancel(id);
+                }
+            }
+        }
+        if let (Some(best_bid), Some(best_ask)) =
+            (order_book.buys().first(), order_book.sells().first())
+        {
+            assert!(
+                best_bid.unit_price < best_ask.unit_price,
+                "book is crossed: {best_bid:?} >= {best_ask:?}"
+            )
+        }
+        validate(order_book);
+    }
+}
+
+/// Apply each of `ops` to both books, asserting that they behave identically.
+/// Each book assigns its own ids, so those from `left` are translated to those from `right`.
+pub fn run_differential<LeftT, RightT, LeftIdT, RightIdT>(
+    left: &mut LeftT,
+    right: &mut RightT,
+    ops: Vec<Op>,
+) where
+    LeftT: ReportingOrderBookApi<usize, usize, LeftIdT>
+        + FillReportingOrderBookApi<usize, usize, LeftIdT>,
+    RightT: ReportingOrderBookApi<usize, usize, RightIdT>
+        + FillReportingOrderBookApi<usize, usize, RightIdT>,
+    LeftIdT: Clone + Debug + Eq + Hash,
+    RightIdT: Clone + Debug + PartialEq,
+{
+    let mut left_to_right = HashMap::new();
+    let mut entered = Vec::new();
+    for op in ops {
+        match op {
+            Op::Buy {
+                quantity,
+                unit_price,
+            } => {
+                let quantity = numwit::Positive::new(quantity).unwrap();
+                let (left_outcome, left_fills) = left
+                    .conditional_buy_reporting_fills(quantity, unit_price, |_| {
+                        ControlFlow::<()>::Continue(())
+                    })
+                    .unwrap();
+                let (right_outcome, right_fills) = right
+                    .conditional_buy_reporting_fills(quantity, unit_price, |_| {
+                        ControlFlow::<()>::Continue(())
+                    })
+                    .unwrap();
+                if let (
+                    BuyEntryOrExecution::EnteredOrderBook { id: left_id }
+                    | BuyEntryOrExecution::PartiallyExecutedThenEntered { id: left_id, .. },
+                    BuyEntryOrExecution::EnteredOrderBook { id: right_id }
+                    | BuyEntryOrExecution::PartiallyExecutedThenEntered { id: right_id, .. },
+                ) = (&left_outcome, &right_outcome)
+                {
+                    left_to_right.insert(left_id.clone(), right_id.clone());
+                    entered.push(left_id.clone());
+                }
+                assert_eq!(translate_buy(left_outcome, &left_to_right), right_outcome);
+                assert_eq!(translate_fills(left_fills, &left_to_right), right_fills);
+            }
+            Op::Sell {
+                quantity,
+                unit_price,
+            } => {
+                let quantity = numwit::Positive::new(quantity).unwrap();
+                let (left_outcome, left_fills) = left
+                    .conditional_sell_reporting_fills(quantity, unit_price, |_| {
+                        ControlFlow::<()>::Continue(())
+                    })
+                    .unwrap();
+                let (right_outcome, right_fills) = right
+                    .conditional_sell_reporting_fills(quantity, unit_price, |_| {
+                        ControlFlow::<()>::Continue(())
+                    })
+                    .unwrap();
+                if let (
+                    SellEntryOrExecution::EnteredOrderBook { id: left_id }
+                    | SellEntryOrExecution::PartiallyExecutedThenEntered { id: left_id, .. },
+                    SellEntryOrExecution::EnteredOrderBook { id: right_id }
+                    | SellEntryOrExecution::PartiallyExecutedThenEntered { id: right_id, .. },
+                ) = (&left_outcome, &right_outcome)
+                {
+                    left_to_right.insert(left_id.clone(), right_id.clone());
+                    entered.push(left_id.clone());
+                }
+                assert_eq!(translate_sell(left_outcome, &left_to_right), right_outcome);
+                assert_eq!(translate_fills(left_fills, &left_to_right), right_fills);
+            }
+            Op::Cancel(index) => {
+                if !entered.is_empty() {
+                    let left_id = entered.swap_remove(index.index(entered.len()));
+                    let right_id = left_to_right[&left_id].clone();
+                    assert_eq!(left.cancel(left_id), right.cancel(right_id));
+                }
+            }
+        }
+        assert_eq!(translate_orders(left.buys(), &left_to_right), right.buys());
+        assert_eq!(
+            translate_orders(left.sells(), &left_to_right),
+            right.sells()
+        );
+    }
+}
+
+fn translate_buy<LeftIdT: Eq + Hash, RightIdT: Clone>(
+    outcome: BuyEntryOrExecution<usize, usize, LeftIdT>,
+    left_to_right: &HashMap<LeftIdT, RightIdT>,
+) -> BuyEntryOrExecution<usize, usize, RightIdT> {
+    let translate = |id| left_to_right[&id].clone();
+    match outcome {
+        BuyEntryOrExecution::EnteredOrderBook { id } => {
+            BuyEntryOrExecution::EnteredOrderBook { id: translate(id) }
+        }
+        BuyEntryOrExecution::MutualFullExecution { seller, spread } => {
+            BuyEntryOrExecution::MutualFullExecution {
+                seller: translate(seller),
+                spread,
+            }
+        }
+        BuyEntryOrExecution::BuyerFullyExecuted {
+            seller,
+            spread,
+            sellers_remaining,
+        } => BuyEntryOrExecution::BuyerFullyExecuted {
+            seller: translate(seller),
+            spread,
+            sellers_remaining,
+        },
+        BuyEntryOrExecution::SellerFullyExecuted {
+            seller,
+            spread,
+            buyers_remaining,
+        } => BuyEntryOrExecution::SellerFullyExecuted {
+            seller: translate(seller),
+            spread,
+            buyers_remaining,
+        },
+        BuyEntryOrExecution::PartiallyExecutedThenEntered {
+            id,
+            seller,
+            spread,
+            buyers_remaining,
+        } => BuyEntryOrExecution::PartiallyExecutedThenEntered {
+            id: translate(id),
+            seller: translate(seller),
+            spread,
+            buyers_remaining,
+        },
+        BuyEntryOrExecution::Rejected => BuyEntryOrExecution::Rejected,
+    }
+}
+
+fn translate_sell<LeftIdT: Eq + Hash, RightIdT: Clone>(
+    outcome: SellEntryOrExecution<usize, usize, LeftIdT>,
+    left_to_right: &HashMap<LeftIdT, RightIdT>,
+) -> SellEntryOrExecution<usize, usize, RightIdT> {
+    let translate = |id| left_to_right[&id].clone();
+    match outcome {
+        SellEntryOrExecution::EnteredOrderBook { id } => {
+            SellEntryOrExecution::EnteredOrderBook { id: translate(id) }
+        }
+        SellEntryOrExecution::MutualFullExecution { buyer, spread } => {
+            SellEntryOrExecution::MutualFullExecution {
+                buyer: translate(buyer),
+                spread,
+            }
+        }
+        SellEntryOrExecution::BuyerFullyExecuted {
+            buyer,
+            spread,
+            sellers_remaining,
+        } => SellEntryOrExecution::BuyerFullyExecuted {
+            buyer: translate(buyer),
+            spread,
+            sellers_remaining,
+        },
+        SellEntryOrExecution::SellerFullyExecuted {
+            buyer,
+            spread,
+            buyers_remaining,
+        } => SellEntryOrExecution::SellerFullyExecuted {
+            buyer: translate(buyer),
+            spread,
+            buyers_remaining,
+        },
+        SellEntryOrExecution::PartiallyExecutedThenEntered {
+            id,
+            buyer,
+            spread,
+            sellers_remaining,
+        } => SellEntryOrExecution::PartiallyExecutedThenEntered {
+            id: translate(id),
+            buyer: translate(buyer),
+            spread,
+            sellers_remaining,
+        },
+        SellEntryOrExecution::Rejected => SellEntryOrExecution::Rejected,
+    }
+}
+
+fn translate_orders<LeftIdT: Eq + Hash, RightIdT: Clone>(
+    orders: Vec<Order<usize, usize, LeftIdT>>,
+    left_to_right: &HashMap<LeftIdT, RightIdT>,
+) -> Vec<Order<usize, usize, RightIdT>> {
+    orders
+        .into_iter()
+        .map(
+            |Order {
+                 quantity,
+                 unit_price,
+                 id,
+             }| Order {
+                quantity,
+                unit_price,
+                id: left_to_right[&id].clone(),
+            },
+        )
+        .collect()
+}
+
+fn translate_fills<LeftIdT: Eq + Hash, RightIdT: Clone>(
+    fills: Vec<FillReport<usize, usize, LeftIdT>>,
+    left_to_right: &HashMap<LeftIdT, RightIdT>,
+) -> Vec<FillReport<usize, usize, RightIdT>> {
+    fills
+        .into_iter()
+        .map(
+            |FillReport {
+                 maker,
+                 unit_price,
+                 quantity,
+                 taker_remaining,
+                 maker_remaining,
+                 price_improvement,
+             }| FillReport {
+                maker: left_to_right[&maker].clone(),
+                unit_price,
+                quantity,
+                taker_remaining,
+                maker_remaining,
+                price_improvement,
+            },
+        )
+        .collect()
+}
@@ -0,0 +1,429 @@
+//! A deliberately naive order book, for differential testing against the real implementations.
+//! Each side is a single [`Vec`], kept in priority order, and everything is a linear scan.
+
+use crate::api::{
+    BuyEntryOrExecution, BuyOrSell, BuyWithFills, Cancelled, ConditionalBuyArgs,
+    ConditionalSellArgs, FillReport, FillReportingOrderBookApi, NoSuchOrder, Order, OrderBookApi,
+    ReportingOrderBookApi, SellEntryOrExecution, SellWithFills,
+};
+use num::Unsigned;
+use numwit::Positive;
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    ops::{self, ControlFlow},
+};
+
+#[derive(Debug, Clone)]
+pub struct ReferenceOrderBook<QuantityT, PriceT> {
+    /// most-generous first
+    buys: Vec<Order<QuantityT, PriceT, uuid::Uuid>>,
+    /// cheapest first
+    sells: Vec<Order<QuantityT, PriceT, uuid::Uuid>>,
+}
+
+impl<QuantityT, PriceT> Default for ReferenceOrderBook<QuantityT, PriceT> {
+    fn default() -> Self {
+        Self {
+            buys: Vec::new(),
+            sells: Vec::new(),
+        }
+    }
+}
+
+/// The spread between the two prices, if there is one
+fn spread<PriceT>(high: PriceT, low: PriceT) -> Option<Positive<PriceT>>
+where
+    PriceT: Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+{
+    match high.cmp(&low) {
+        Ordering::Less => unreachable!("prices do not cross"),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(Positive::new(high - low).unwrap()),
+    }
+}
+
+/// A fill against `maker`, which has already had its quantity reduced
+fn fill<QuantityT: Clone, PriceT: Clone>(
+    maker: &Order<QuantityT, PriceT, uuid::Uuid>,
+    quantity: QuantityT,
+    taker_remaining: QuantityT,
+    price_improvement: Option<Positive<PriceT>>,
+) -> FillReport<QuantityT, PriceT, uuid::Uuid> {
+    FillReport {
+        maker: maker.id,
+        unit_price: maker.unit_price.clone(),
+        quantity,
+        taker_remaining,
+        maker_remaining: maker.quantity.clone(),
+        price_improvement,
+    }
+}
+
+impl<QuantityT, PriceT: Ord> ReferenceOrderBook<QuantityT, PriceT> {
+    /// Add a buy behind every buy at the same price or better.
+    fn rest_buy(&mut self, quantity: QuantityT, unit_price: PriceT) -> uuid::Uuid {
+        let id = uuid::Uuid::new_v4();
+        let position = self
+            .buys
+            .iter()
+            .position(|it| it.unit_price < unit_price)
+            .unwrap_or(self.buys.len());
+        self.buys.insert(
+            position,
+            Order {
+                quantity,
+                unit_price,
+                id,
+            },
+        );
+        id
+    }
+
+    /// Add a sell behind every sell at the same price or better.
+    fn rest_sell(&mut self, quantity: QuantityT, unit_price: PriceT) -> uuid::Uuid {
+        let id = uuid::Uuid::new_v4();
+        let position = self
+            .sells
+            .iter()
+            .position(|it| it.unit_price > unit_price)
+            .unwrap_or(self.sells.len());
+        self.sells.insert(
+            position,
+            Order {
+                quantity,
+                unit_price,
+                id,
+            },
+        );
+        id
+    }
+}
+
+impl<QuantityT, PriceT> FillReportingOrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for ReferenceOrderBook<QuantityT, PriceT>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+{
+    fn conditional_buy_reporting_fills<BuyAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalBuyArgs<'_, uuid::Uuid>) -> ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyWithFills<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
+        let quantity = quantity.into_inner();
+        match self.sells.first() {
+            Some(ask) if ask.unit_price <= unit_price => {
+                if let ControlFlow::Break(reason) =
+                    condition(ConditionalBuyArgs { seller_id: &ask.id })
+                {
+                    return Err(reason);
+                }
+                let mut buyers_remaining = quantity;
+                let mut fills = Vec::new();
+                loop {
+                    let seller = self.sells[0].id;
+                    // each level trades at its own price
+                    let spread = spread(unit_price.clone(), self.sells[0].unit_price.clone());
+                    match buyers_remaining.cmp(&self.sells[0].quantity) {
+                        Ordering::Less => {
+                            let ask = &mut self.sells[0];
+                            ask.quantity = ask.quantity.clone() - buyers_remaining.clone();
+                            fills.push(fill(
+                                ask,
+                                buyers_remaining,
+                                QuantityT::zero(),
+                                spread.clone(),
+                            ));
+                            break Ok((
+                                BuyEntryOrExecution::BuyerFullyExecuted {
+                                    seller,
+                                    spread,
+                                    sellers_remaining: ask.quantity.clone(),
+                                },
+                                fills,
+                            ));
+                        }
+                        Ordering::Equal => {
+                            let mut ask = self.sells.remove(0);
+                            ask.quantity = QuantityT::zero();
+                            fills.push(fill(
+                                &ask,
+                                buyers_remaining,
+                                QuantityT::zero(),
+                                spread.clone(),
+                            ));
+                            break Ok((
+                                BuyEntryOrExecution::MutualFullExecution { seller, spread },
+                                fills,
+                            ));
+                        }
+                        Ordering::Greater => {
+                            let mut ask = self.sells.remove(0);
+                            let filled = std::mem::replace(&mut ask.quantity, QuantityT::zero());
+                            buyers_remaining = buyers_remaining - filled.clone();
+                            fills.push(fill(
+                                &ask,
+                                filled,
+                                buyers_remaining.clone(),
+                                spread.clone(),
+                            ));
+                            if self
+                                .sells
+                                .first()
+                                .is_none_or(|it| it.unit_price > unit_price)
+                            {
+                                let id = self.rest_buy(buyers_remaining.clone(), unit_price);
+                                break Ok((
+                                    BuyEntryOrExecution::PartiallyExecutedThenEntered {
+                                        id,
+                                        seller,
+                                        spread,
+                                        buyers_remaining,
+                                    },
+                                    fills,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            Some(_) | None => {
+                let id = self.rest_buy(quantity, unit_price);
+                Ok((BuyEntryOrExecution::EnteredOrderBook { id }, vec![]))
+            }
+        }
+    }
+
+    fn conditional_sell_reporting_fills<SellAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalSellArgs<'_, uuid::Uuid>) -> ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellWithFills<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
+        let quantity = quantity.into_inner();
+        match self.buys.first() {
+            Some(bid) if bid.unit_price >= unit_price => {
+                if let ControlFlow::Break(reason) =
+                    condition(ConditionalSellArgs { buyer_id: &bid.id })
+                {
+                    return Err(reason);
+                }
+                let mut sellers_remaining = quantity;
+                let mut fills = Vec::new();
+                loop {
+                    let buyer = self.buys[0].id;
+                    // each level trades at its own price
+                    let spread = spread(self.buys[0].unit_price.clone(), unit_price.clone());
+                    match sellers_remaining.cmp(&self.buys[0].quantity) {
+                        Ordering::Less => {
+                            let bid = &mut self.buys[0];
+                            bid.quantity = bid.quantity.clone() - sellers_remaining.clone();
+                            fills.push(fill(
+                                bid,
+                                sellers_remaining,
+                                QuantityT::zero(),
+                                spread.clone(),
+                            ));
+                            break Ok((
+                                SellEntryOrExecution::SellerFullyExecuted {
+                                    buyer,
+                                    spread,
+                                    buyers_remaining: bid.quantity.clone(),
+                                },
+                                fills,
+                            ));
+                        }
+                        Ordering::Equal => {
+                            let mut bid = self.buys.remove(0);
+                            bid.quantity = QuantityT::zero();
+                            fills.push(fill(
+                                &bid,
+                                sellers_remaining,
+                                QuantityT::zero(),
+                                spread.clone(),
+                            ));
+                            break Ok((
+                                SellEntryOrExecution::MutualFullExecution { buyer, spread },
+                                fills,
+                            ));
+                        }
+                        Ordering::Greater => {
+                            let mut bid = self.buys.remove(0);
+                            let filled = std::mem::replace(&mut bid.quantity, QuantityT::zero());
+                            sellers_remaining = sellers_remaining - filled.clone();
+                            fills.push(fill(
+                                &bid,
+                                filled,
+                                sellers_remaining.clone(),
+                                spread.clone(),
+                            ));
+                            if self
+                                .buys
+                                .first()
+                                .is_none_or(|it| it.unit_price < unit_price)
+                            {
+                                let id = self.rest_sell(sellers_remaining.clone(), unit_price);
+                                break Ok((
+                                    SellEntryOrExecution::PartiallyExecutedThenEntered {
+                                        id,
+                                        buyer,
+                                        spread,
+                                        sellers_remaining,
+                                    },
+                                    fills,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            Some(_) | None => {
+                let id = self.rest_sell(quantity, unit_price);
+                Ok((SellEntryOrExecution::EnteredOrderBook { id }, vec![]))
+            }
+        }
+    }
+}
+
+impl<QuantityT, PriceT> OrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for ReferenceOrderBook<QuantityT, PriceT>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+{
+    fn conditional_buy<BuyAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalBuyArgs<'_, uuid::Uuid>) -> ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyEntryOrExecution<QuantityT, PriceT, uuid::Uuid>, BuyAbortReasonT> {
+        self.conditional_buy_reporting_fills(quantity, unit_price, condition)
+            .map(|(entry_or_exc, _fills)| entry_or_exc)
+    }
+
+    fn conditional_sell<SellAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalSellArgs<'_, uuid::Uuid>) -> ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellEntryOrExecution<QuantityT, PriceT, uuid::Uuid>, SellAbortReasonT> {
+        self.conditional_sell_reporting_fills(quantity, unit_price, condition)
+            .map(|(entry_or_exc, _fills)| entry_or_exc)
+    }
+
+    fn query(&self, id: uuid::Uuid) -> Result<BuyOrSell<QuantityT, PriceT>, NoSuchOrder> {
+        if let Some(buy) = self.buys.iter().find(|it| it.id == id) {
+            return Ok(BuyOrSell::Buy {
+                quantity: buy.quantity.clone(),
+                unit_price: buy.unit_price.clone(),
+            });
+        }
+        if let Some(sell) = self.sells.iter().find(|it| it.id == id) {
+            return Ok(BuyOrSell::Sell {
+                quantity: sell.quantity.clone(),
+                unit_price: sell.unit_price.clone(),
+            });
+        }
+        Err(NoSuchOrder)
+    }
+
+    fn cancel(&mut self, id: uuid::Uuid) -> Result<Cancelled, NoSuchOrder> {
+        for side in [&mut self.buys, &mut self.sells] {
+            if let Some(position) = side.iter().position(|it| it.id == id) {
+                side.remove(position);
+                return Ok(Cancelled);
+            }
+        }
+        Err(NoSuchOrder)
+    }
+}
+
+impl<QuantityT, PriceT> ReportingOrderBookApi<QuantityT, PriceT, uuid::Uuid>
+    for ReferenceOrderBook<QuantityT, PriceT>
+where
+    QuantityT: Unsigned + Clone + Ord + Debug,
+    PriceT: Clone + Ord + Debug + ops::Sub<Output = PriceT> + num::Zero,
+{
+    fn iter_buys(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, uuid::Uuid>> + '_ {
+        self.buys.iter().cloned()
+    }
+
+    fn iter_sells(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, uuid::Uuid>> + '_ {
+        self.sells.iter().cloned()
+    }
+
+    fn buys(&self) -> Vec<Order<QuantityT, PriceT, uuid::Uuid>> {
+        self.buys.clone()
+    }
+
+    fn sells(&self) -> Vec<Order<QuantityT, PriceT, uuid::Uuid>> {
+        self.sells.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReferenceOrderBook;
+    use crate::{
+        price_level_b_tree_order_book::PriceLevelBTreeOrderBook, test_suite::do_test_suite,
+    };
+
+    do_test_suite! {ReferenceOrderBook<usize, usize> {
+        default_is_empty,
+        add_query_remove_single_buy_order,
+        add_query_remove_single_sell_order,
+        single_resident_buy_is_fully_executed,
+        single_resident_sell_is_fully_executed,
+        buys_reported_with_price_time_priority,
+        sells_reported_with_price_time_priority,
+        buys_execute_with_price_time_priority,
+        sells_execute_with_price_time_priority,
+        fill_reports_partially_executed_maker,
+        fill_reports_fully_executed_maker,
+        aborted_conditional_buy_leaves_book_unchanged,
+        aborted_conditional_sell_leaves_book_unchanged,
+        buy_consumes_successive_sells_at_one_price,
+        sell_consumes_successive_buys_at_one_price,
+        partially_executed_buy_keeps_its_place_in_buys,
+        partially_executed_sell_keeps_its_place_in_sells,
+        partially_filled_buy_remainder_stays_a_buy,
+        cancelling_middle_sell_keeps_time_priority,
+        fill_reports_price_improvement,
+        try_buy_and_sell_reject_zero_quantity,
+        buy_at_resting_price_joins_back_of_level,
+        sell_at_resting_price_joins_back_of_level,
+    }}
+
+    #[test]
+    fn differently_built_books_have_equal_ladders() {
+        use crate::api::{ReportingOrderBookApi as _, UnconditionalOrderBookApi as _};
+        use numwit::Positive;
+
+        let positive = |quantity: usize| Positive::new(quantity).unwrap();
+        let mut reference = ReferenceOrderBook::<usize, usize>::default();
+        reference.unconditional_buy(positive(2), 8);
+        reference.unconditional_sell(positive(1), 10);
+        let mut order_book = PriceLevelBTreeOrderBook::default();
+        order_book.unconditional_sell(positive(3), 10);
+        order_book.unconditional_buy(positive(2), 10);
+        order_book.unconditional_buy(positive(2), 8);
+        assert!(reference.ladder_eq(&order_book));
+        assert!(!reference.logically_eq(&order_book));
+        assert!(reference.logically_eq(&reference.clone()));
+        order_book.unconditional_buy(positive(1), 7);
+        assert!(!reference.ladder_eq(&order_book));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn agrees_with_price_level_b_tree_order_book(ops in crate::property_suite::ops()) {
+            crate::property_suite::run_differential(
+                &mut ReferenceOrderBook::default(),
+                &mut PriceLevelBTreeOrderBook::default(),
+                ops,
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::{fmt, ops};
+
+/// A decimal price with `DECIMALS` digits after the point, stored as an integer number of the smallest increment.
+///
+/// `ScaledPrice::<4>::from_scaled(12345)` is `1.2345`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ScaledPrice<const DECIMALS: u32>(i64);
+
+impl<const DECIMALS: u32> ScaledPrice<DECIMALS> {
+    /// The number of increments in one whole unit.
+    pub const SCALE: i64 = 10_i64.pow(DECIMALS);
+
+    pub const fn from_scaled(scaled: i64) -> Self {
+        Self(scaled)
+    }
+    pub const fn scaled(self) -> i64 {
+        self.0
+    }
+}
+
+impl<const DECIMALS: u32> ops::Add for ScaledPrice<DECIMALS> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl<const DECIMALS: u32> ops::Sub for ScaledPrice<DECIMALS> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl<const DECIMALS: u32> ops::Rem for ScaledPrice<DECIMALS> {
+    type Output = Self;
+    fn rem(self, rhs: Self) -> Self::Output {
+        Self(self.0 % rhs.0)
+    }
+}
+
+impl<const DECIMALS: u32> num::Zero for ScaledPrice<DECIMALS> {
+    fn zero() -> Self {
+        Self(0)
+    }
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Conversions are to whole units, so truncate.
+impl<const DECIMALS: u32> num::ToPrimitive for ScaledPrice<DECIMALS> {
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.0 / Self::SCALE)
+    }
+    fn to_u64(&self) -> Option<u64> {
+        self.to_i64()?.try_into().ok()
+    }
+    fn to_f64(&self) -> Option<f64> {
+        Some(self.0 as f64 / Self::SCALE as f64)
+    }
+}
+
+impl<const DECIMALS: u32> crate::execution_metrics::Measurable for ScaledPrice<DECIMALS> {
+    fn as_u64(&self) -> Option<u64> {
+        num::ToPrimitive::to_u64(self)
+    }
+    fn as_f64(&self) -> Option<f64> {
+        num::ToPrimitive::to_f64(self)
+    }
+}
+
+impl<const DECIMALS: u32> fmt::Display for ScaledPrice<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let whole = (self.0 / Self::SCALE).unsigned_abs();
+        match DECIMALS {
+            0 => write!(f, "{sign}{whole}"),
+            _ => {
+                let fraction = (self.0 % Self::SCALE).unsigned_abs();
+                write!(
+                    f,
+                    "{sign}{whole}.{fraction:0width$}",
+                    width = DECIMALS as usize
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScaledPrice;
+    use crate::{
+        api::{BuyEntryOrExecution, UnconditionalOrderBookApi as _},
+        price_level_b_tree_order_book::PriceLevelBTreeOrderBook,
+    };
+    use numwit::Positive;
+    use pretty_assertions::assert_eq;
+
+    type Price = ScaledPrice<4>;
+
+    #[test]
+    fn display() {
+        assert_eq!("1.2345", Price::from_scaled(12345).to_string());
+        assert_eq!("0.0005", Price::from_scaled(5).to_string());
+        assert_eq!("-0.0005", Price::from_scaled(-5).to_string());
+        assert_eq!("12", ScaledPrice::<0>::from_scaled(12).to_string());
+    }
+
+    #[test]
+    fn matches_with_scaled_spread() {
+        let mut order_book = PriceLevelBTreeOrderBook::<usize, Price, _>::default();
+        let seller = *order_book
+            .unconditional_sell(Positive::new(1).unwrap(), Price::from_scaled(10_050))
+            .as_entered_order_book()
+            .unwrap();
+        let BuyEntryOrExecution::MutualFullExecution {
+            seller: matched,
+            spread,
+        } = order_book.unconditional_buy(Positive::new(1).unwrap(), Price::from_scaled(10_125))
+        else {
+            panic!("orders should have matched")
+        };
+        assert_eq!(seller, matched);
+        assert_eq!("0.0075", spread.unwrap().into_inner().to_string());
+    }
+}
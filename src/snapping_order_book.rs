@@ -0,0 +1,194 @@
+use numwit::Positive;
+use std::{
+    fmt::Debug,
+    ops::{self, ControlFlow},
+};
+
+use crate::api::{
+    BuyEntryOrExecution, BuyOrSell, Cancelled, ConditionalBuyArgs, ConditionalSellArgs,
+    NoSuchOrder, Order, OrderBookApi, ReportingOrderBookApi, SellEntryOrExecution,
+};
+
+/// How to move a price which is between ticks onto the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    Down,
+    Up,
+    /// Halfway rounds up.
+    Nearest,
+}
+
+/// Wraps an order book, moving the price of each submitted order onto a grid of `tick_size` before it reaches the book.
+///
+/// By default buys round down and sells round up, so that no order trades at a worse price than it asked for.
+#[derive(Debug, Clone)]
+pub struct SnappingOrderBook<OrderBookT, PriceT> {
+    inner: OrderBookT,
+    tick_size: PriceT,
+    buy_rounding: Rounding,
+    sell_rounding: Rounding,
+}
+
+impl<OrderBookT, PriceT> SnappingOrderBook<OrderBookT, PriceT> {
+    /// # Panics
+    /// Submitting an order will panic if `tick_size` is zero.
+    pub fn new(inner: OrderBookT, tick_size: PriceT) -> Self {
+        Self {
+            inner,
+            tick_size,
+            buy_rounding: Rounding::Down,
+            sell_rounding: Rounding::Up,
+        }
+    }
+    pub fn with_buy_rounding(self, buy_rounding: Rounding) -> Self {
+        Self {
+            buy_rounding,
+            ..self
+        }
+    }
+    pub fn with_sell_rounding(self, sell_rounding: Rounding) -> Self {
+        Self {
+            sell_rounding,
+            ..self
+        }
+    }
+    pub fn inner(&self) -> &OrderBookT {
+        &self.inner
+    }
+    pub fn into_inner(self) -> OrderBookT {
+        self.inner
+    }
+}
+
+impl<OrderBookT, PriceT> SnappingOrderBook<OrderBookT, PriceT>
+where
+    PriceT: Clone + Ord + num::Zero + ops::Sub<Output = PriceT> + ops::Rem<Output = PriceT>,
+{
+    /// Move `price` onto the tick grid. Prices already on the grid are unchanged.
+    pub fn snap(&self, price: PriceT, rounding: Rounding) -> PriceT {
+        let tick_size = self.tick_size.clone();
+        let offset = match price.clone() % tick_size.clone() {
+            // remainders take the sign of `price`
+            negative if negative < PriceT::zero() => negative + tick_size.clone(),
+            offset => offset,
+        };
+        if offset.is_zero() {
+            return price;
+        }
+        let down = price - offset.clone();
+        match rounding {
+            Rounding::Down => down,
+            Rounding::Up => down + tick_size,
+            Rounding::Nearest => match offset.clone() + offset >= tick_size {
+                true => down + tick_size,
+                false => down,
+            },
+        }
+    }
+}
+
+impl<OrderBookT, QuantityT, PriceT, OrderIdT> OrderBookApi<QuantityT, PriceT, OrderIdT>
+    for SnappingOrderBook<OrderBookT, PriceT>
+where
+    OrderBookT: OrderBookApi<QuantityT, PriceT, OrderIdT>,
+    PriceT: Clone + Ord + num::Zero + ops::Sub<Output = PriceT> + ops::Rem<Output = PriceT>,
+{
+    fn conditional_buy<BuyAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalBuyArgs<'_, OrderIdT>) -> ControlFlow<BuyAbortReasonT, ()>,
+    ) -> Result<BuyEntryOrExecution<QuantityT, PriceT, OrderIdT>, BuyAbortReasonT> {
+        let unit_price = self.snap(unit_price, self.buy_rounding);
+        self.inner.conditional_buy(quantity, unit_price, condition)
+    }
+
+    fn conditional_sell<SellAbortReasonT: Debug>(
+        &mut self,
+        quantity: Positive<QuantityT>,
+        unit_price: PriceT,
+        condition: impl FnOnce(ConditionalSellArgs<'_, OrderIdT>) -> ControlFlow<SellAbortReasonT, ()>,
+    ) -> Result<SellEntryOrExecution<QuantityT, PriceT, OrderIdT>, SellAbortReasonT> {
+        let unit_price = self.snap(unit_price, self.sell_rounding);
+        self.inner.conditional_sell(quantity, unit_price, condition)
+    }
+
+    fn query(&self, id: OrderIdT) -> Result<BuyOrSell<QuantityT, PriceT>, NoSuchOrder> {
+        self.inner.query(id)
+    }
+
+    fn cancel(&mut self, id: OrderIdT) -> Result<Cancelled, NoSuchOrder> {
+        self.inner.cancel(id)
+    }
+}
+
+impl<OrderBookT, QuantityT, PriceT, OrderIdT> ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>
+    for SnappingOrderBook<OrderBookT, PriceT>
+where
+    OrderBookT: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>,
+    PriceT: Clone + Ord + num::Zero + ops::Sub<Output = PriceT> + ops::Rem<Output = PriceT>,
+{
+    fn iter_buys(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>> + '_ {
+        self.inner.iter_buys()
+    }
+
+    fn iter_sells(&self) -> impl Iterator<Item = Order<QuantityT, PriceT, OrderIdT>> + '_ {
+        self.inner.iter_sells()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rounding, SnappingOrderBook};
+    use crate::{
+        api::{BuyOrSell, OrderBookApi as _, UnconditionalOrderBookApi as _},
+        price_level_b_tree_order_book::PriceLevelBTreeOrderBook,
+        scaled_price::ScaledPrice,
+    };
+    use numwit::Positive;
+    use pretty_assertions::assert_eq;
+
+    type Price = ScaledPrice<1>;
+
+    fn book() -> SnappingOrderBook<PriceLevelBTreeOrderBook<usize, Price, uuid::Uuid>, Price> {
+        SnappingOrderBook::new(Default::default(), Price::from_scaled(5))
+    }
+
+    #[test]
+    fn buys_round_down_and_sells_round_up() {
+        let mut order_book = book();
+        let buy = *order_book
+            .unconditional_buy(Positive::new(1).unwrap(), Price::from_scaled(1007))
+            .as_entered_order_book()
+            .unwrap();
+        let sell = *order_book
+            .unconditional_sell(Positive::new(1).unwrap(), Price::from_scaled(1007))
+            .as_entered_order_book()
+            .unwrap();
+        assert_eq!(
+            Ok(BuyOrSell::Buy {
+                quantity: 1,
+                unit_price: Price::from_scaled(1005)
+            }),
+            order_book.query(buy)
+        );
+        assert_eq!(
+            Ok(BuyOrSell::Sell {
+                quantity: 1,
+                unit_price: Price::from_scaled(1010)
+            }),
+            order_book.query(sell)
+        );
+    }
+
+    #[test]
+    fn snap() {
+        let order_book = book();
+        let snap = |scaled, rounding| order_book.snap(Price::from_scaled(scaled), rounding);
+        assert_eq!(Price::from_scaled(1005), snap(1005, Rounding::Up));
+        assert_eq!(Price::from_scaled(1005), snap(1007, Rounding::Nearest));
+        assert_eq!(Price::from_scaled(1010), snap(1008, Rounding::Nearest));
+        assert_eq!(Price::from_scaled(-10), snap(-7, Rounding::Down));
+        assert_eq!(Price::from_scaled(-5), snap(-7, Rounding::Up));
+    }
+}
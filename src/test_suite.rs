@@ -1,11 +1,14 @@
 use num::{One, Zero};
 use numwit::Positive;
 use pretty_assertions::assert_eq;
-use std::fmt::{self, Debug};
+use std::{
+    fmt::{self, Debug},
+    ops::ControlFlow,
+};
 
 use crate::api::{
-    BuyEntryOrExecution, BuyOrSell, Order, OrderBookApi, ReportingOrderBookApi,
-    SellEntryOrExecution, UnconditionalOrderBookApi,
+    BuyEntryOrExecution, BuyOrSell, FillReport, FillReportingOrderBookApi, Order, OrderBookApi,
+    ReportingOrderBookApi, SellEntryOrExecution, UnconditionalOrderBookApi, ZeroQuantity,
 };
 
 struct OrderMatcher<QuantityT, PriceT, OrderIdT> {
@@ -88,7 +91,7 @@ fn is_empty<T, QuantityT, PriceT, OrderIdT>(order_book: &T) -> bool
 where
     T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>,
 {
-    order_book.buys().is_empty() && order_book.sells().is_empty()
+    order_book.iter_buys().next().is_none() && order_book.iter_sells().next().is_none()
 }
 fn buy_unexecuted<T, QuantityT, PriceT, OrderIdT>(
     order_book: &mut T,
@@ -122,6 +125,38 @@ where
         .into_entered_order_book()
         .expect("sell should not have executed")
 }
+fn buy_reporting_fills<T, QuantityT, PriceT, OrderIdT>(
+    order_book: &mut T,
+    quantity: QuantityT,
+    unit_price: PriceT,
+) -> Vec<FillReport<QuantityT, PriceT, OrderIdT>>
+where
+    T: FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT>,
+    QuantityT: Debug + PartialOrd + Zero,
+{
+    let (_, fills) = order_book
+        .conditional_buy_reporting_fills(positive(quantity), unit_price, |_| {
+            ControlFlow::<()>::Continue(())
+        })
+        .unwrap();
+    fills
+}
+fn sell_reporting_fills<T, QuantityT, PriceT, OrderIdT>(
+    order_book: &mut T,
+    quantity: QuantityT,
+    unit_price: PriceT,
+) -> Vec<FillReport<QuantityT, PriceT, OrderIdT>>
+where
+    T: FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT>,
+    QuantityT: Debug + PartialOrd + Zero,
+{
+    let (_, fills) = order_book
+        .conditional_sell_reporting_fills(positive(quantity), unit_price, |_| {
+            ControlFlow::<()>::Continue(())
+        })
+        .unwrap();
+    fills
+}
 fn positive<T>(t: T) -> Positive<T>
 where
     T: Debug + Zero + PartialOrd,
@@ -129,6 +164,22 @@ where
     Positive::new(t).unwrap()
 }
 
+#[cfg(test)]
+macro_rules! do_test_suite {
+    ($ty:ty {
+        $($fn_name:ident),* $(,)?
+    }) => {
+        $(
+            #[test_log::test]
+            fn $fn_name() {
+                $crate::test_suite::$fn_name::<$ty, _, _, _>();
+            }
+        )*
+    };
+}
+#[cfg(test)]
+pub(crate) use do_test_suite;
+
 ////////////////
 // Test suite //
 ////////////////
@@ -329,3 +380,396 @@ where
         order_book.unconditional_buy(one(), two())
     );
 }
+
+pub fn fill_reports_partially_executed_maker<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let maker = sell_unexecuted(&mut order_book, two(), one());
+    assert_eq!(
+        vec![FillReport {
+            maker,
+            unit_price: one(),
+            quantity: one(),
+            taker_remaining: QuantityT::zero(),
+            maker_remaining: one(),
+            price_improvement: None,
+        }],
+        buy_reporting_fills(&mut order_book, one(), one()),
+    );
+}
+
+pub fn fill_reports_fully_executed_maker<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let maker = sell_unexecuted(&mut order_book, one(), one());
+    assert_eq!(
+        vec![FillReport {
+            maker,
+            unit_price: one(),
+            quantity: one(),
+            taker_remaining: one(),
+            maker_remaining: QuantityT::zero(),
+            price_improvement: Some(one()),
+        }],
+        buy_reporting_fills(&mut order_book, two(), two()),
+    );
+}
+
+pub fn aborted_conditional_buy_leaves_book_unchanged<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq + PartialOrd,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    buy_unexecuted(&mut order_book, one(), one());
+    let cheap = sell_unexecuted(&mut order_book, one(), two());
+    sell_unexecuted(&mut order_book, two(), two());
+    sell_unexecuted(&mut order_book, one(), two::<PriceT>() + one());
+    let before = order_book.snapshot();
+    assert_eq!(
+        Err("aborted"),
+        order_book.conditional_buy(
+            positive(two::<QuantityT>() + two()),
+            two::<PriceT>() + one(),
+            |args| {
+                assert_eq!(&cheap, args.seller_id);
+                ControlFlow::Break("aborted")
+            }
+        ),
+    );
+    assert_eq!(before, order_book.snapshot());
+}
+
+pub fn aborted_conditional_sell_leaves_book_unchanged<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq + PartialOrd,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    sell_unexecuted(&mut order_book, one(), two::<PriceT>() + one());
+    let generous = buy_unexecuted(&mut order_book, one(), two());
+    buy_unexecuted(&mut order_book, two(), two());
+    buy_unexecuted(&mut order_book, one(), one());
+    let before = order_book.snapshot();
+    assert_eq!(
+        Err("aborted"),
+        order_book.conditional_sell(positive(two::<QuantityT>() + two()), one(), |args| {
+            assert_eq!(&generous, args.buyer_id);
+            ControlFlow::Break("aborted")
+        }),
+    );
+    assert_eq!(before, order_book.snapshot());
+}
+
+pub fn buy_consumes_successive_sells_at_one_price<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>
+        + FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT>
+        + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = sell_unexecuted(&mut order_book, one(), one());
+    let second = sell_unexecuted(&mut order_book, one(), one());
+    let third = sell_unexecuted(&mut order_book, one(), one());
+    assert_eq!(
+        vec![
+            FillReport {
+                maker: first,
+                unit_price: one(),
+                quantity: one(),
+                taker_remaining: one(),
+                maker_remaining: QuantityT::zero(),
+                price_improvement: None,
+            },
+            FillReport {
+                maker: second,
+                unit_price: one(),
+                quantity: one(),
+                taker_remaining: QuantityT::zero(),
+                maker_remaining: QuantityT::zero(),
+                price_improvement: None,
+            }
+        ],
+        buy_reporting_fills(&mut order_book, two(), one()),
+    );
+    assert_eq!(vec![order!(id = third)], order_book.sells());
+}
+
+pub fn sell_consumes_successive_buys_at_one_price<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT>
+        + FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT>
+        + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = buy_unexecuted(&mut order_book, one(), one());
+    let second = buy_unexecuted(&mut order_book, one(), one());
+    let third = buy_unexecuted(&mut order_book, one(), one());
+    assert_eq!(
+        vec![
+            FillReport {
+                maker: first,
+                unit_price: one(),
+                quantity: one(),
+                taker_remaining: one(),
+                maker_remaining: QuantityT::zero(),
+                price_improvement: None,
+            },
+            FillReport {
+                maker: second,
+                unit_price: one(),
+                quantity: one(),
+                taker_remaining: QuantityT::zero(),
+                maker_remaining: QuantityT::zero(),
+                price_improvement: None,
+            }
+        ],
+        sell_reporting_fills(&mut order_book, two(), one()),
+    );
+    assert_eq!(vec![order!(id = third)], order_book.buys());
+}
+
+pub fn partially_executed_buy_keeps_its_place_in_buys<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = buy_unexecuted(&mut order_book, two(), one());
+    let second = buy_unexecuted(&mut order_book, one(), one());
+    order_book.unconditional_sell(one(), one());
+    assert_eq!(
+        vec![
+            order!(id = first, quantity = QuantityT::one()),
+            order!(id = second, quantity = QuantityT::one()),
+        ],
+        order_book.buys()
+    );
+    assert!(order_book.sells().is_empty());
+}
+
+pub fn partially_executed_sell_keeps_its_place_in_sells<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = sell_unexecuted(&mut order_book, two(), one());
+    let second = sell_unexecuted(&mut order_book, one(), one());
+    order_book.unconditional_buy(one(), one());
+    assert_eq!(
+        vec![
+            order!(id = first, quantity = QuantityT::one()),
+            order!(id = second, quantity = QuantityT::one()),
+        ],
+        order_book.sells()
+    );
+    assert!(order_book.buys().is_empty());
+}
+
+pub fn partially_filled_buy_remainder_stays_a_buy<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Clone + Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let resident_buy = buy_unexecuted(&mut order_book, two(), one());
+    assert_eq!(
+        SellEntryOrExecution::SellerFullyExecuted {
+            buyer: resident_buy.clone(),
+            spread: None,
+            buyers_remaining: one(),
+        },
+        order_book.unconditional_sell(one(), one()),
+    );
+    assert_eq!(
+        vec![order!(id = resident_buy.clone(), quantity = one())],
+        order_book.buys()
+    );
+    assert!(order_book.sells().is_empty());
+    assert_eq!(
+        Ok(BuyOrSell::Buy {
+            quantity: one(),
+            unit_price: one()
+        }),
+        order_book.query(resident_buy.clone()),
+    );
+    // and the remainder still trades as a buy
+    assert_eq!(
+        SellEntryOrExecution::MutualFullExecution {
+            buyer: resident_buy,
+            spread: None
+        },
+        order_book.unconditional_sell(one(), one()),
+    );
+    assert!(is_empty(&order_book));
+}
+
+pub fn cancelling_middle_sell_keeps_time_priority<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Debug + PartialEq,
+    OrderIdT: Clone + Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = sell_unexecuted(&mut order_book, one(), one());
+    let middle = sell_unexecuted(&mut order_book, two(), one());
+    let last = sell_unexecuted(&mut order_book, one(), one());
+    assert!(order_book.cancel(middle).is_ok());
+    assert_eq!(
+        vec![order!(id = first), order!(id = last)],
+        order_book.sells()
+    );
+    assert!(order_book.buys().is_empty());
+}
+
+pub fn fill_reports_price_improvement<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: FillReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq + PartialOrd,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let cheaper = sell_unexecuted(&mut order_book, one(), one());
+    let dearer = sell_unexecuted(&mut order_book, one(), two());
+    let three = || PriceT::one() + two();
+    let fill = |maker, unit_price, price_improvement| FillReport {
+        maker,
+        unit_price,
+        quantity: QuantityT::one(),
+        taker_remaining: QuantityT::zero(),
+        maker_remaining: QuantityT::zero(),
+        price_improvement: Some(price_improvement),
+    };
+    assert_eq!(
+        vec![fill(cheaper, one(), Positive::new(two()).unwrap())],
+        buy_reporting_fills(&mut order_book, one(), three()),
+    );
+    assert_eq!(
+        vec![fill(dearer, two(), one())],
+        buy_reporting_fills(&mut order_book, one(), three()),
+    );
+}
+
+pub fn try_buy_and_sell_reject_zero_quantity<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq,
+    OrderIdT: Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    assert_eq!(
+        Err(ZeroQuantity),
+        order_book.try_buy(QuantityT::zero(), one())
+    );
+    assert_eq!(
+        Err(ZeroQuantity),
+        order_book.try_sell(QuantityT::zero(), two())
+    );
+    assert!(is_empty(&order_book));
+    assert!(matches!(
+        order_book.try_buy(one(), one()),
+        Ok(BuyEntryOrExecution::EnteredOrderBook { .. })
+    ));
+    assert!(matches!(
+        order_book.try_sell(one(), two()),
+        Ok(SellEntryOrExecution::EnteredOrderBook { .. })
+    ));
+    assert_eq!(1, order_book.buys().len());
+    assert_eq!(1, order_book.sells().len());
+}
+
+pub fn buy_at_resting_price_joins_back_of_level<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq,
+    OrderIdT: Clone + Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = buy_unexecuted(&mut order_book, one(), one());
+    let second = buy_unexecuted(&mut order_book, two(), one());
+    assert_eq!(
+        vec![
+            order!(id = first.clone(), quantity = one()),
+            order!(id = second.clone(), quantity = two()),
+        ],
+        order_book.buys(),
+    );
+    assert_eq!(
+        Ok(BuyOrSell::Buy {
+            quantity: one(),
+            unit_price: one()
+        }),
+        order_book.query(first),
+    );
+    assert_eq!(
+        Ok(BuyOrSell::Buy {
+            quantity: two(),
+            unit_price: one()
+        }),
+        order_book.query(second),
+    );
+}
+
+pub fn sell_at_resting_price_joins_back_of_level<T, QuantityT, PriceT, OrderIdT>()
+where
+    T: ReportingOrderBookApi<QuantityT, PriceT, OrderIdT> + Default,
+    QuantityT: One + Zero + Debug + PartialEq + PartialOrd,
+    PriceT: One + Zero + Debug + PartialEq,
+    OrderIdT: Clone + Debug + PartialEq,
+{
+    let mut order_book = T::default();
+    let first = sell_unexecuted(&mut order_book, one(), one());
+    let second = sell_unexecuted(&mut order_book, two(), one());
+    assert_eq!(
+        vec![
+            order!(id = first.clone(), quantity = one()),
+            order!(id = second.clone(), quantity = two()),
+        ],
+        order_book.sells(),
+    );
+    assert_eq!(
+        Ok(BuyOrSell::Sell {
+            quantity: one(),
+            unit_price: one()
+        }),
+        order_book.query(first),
+    );
+    assert_eq!(
+        Ok(BuyOrSell::Sell {
+            quantity: two(),
+            unit_price: one()
+        }),
+        order_book.query(second),
+    );
+}
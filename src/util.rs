@@ -2,14 +2,38 @@ use std::collections::{BTreeMap, VecDeque};
 use tap::Tap as _;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
 pub struct NonEmpty<T>(T);
 
+/// Deserialized in order, like a [`VecDeque`], but rejects an empty sequence.
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for NonEmpty<VecDeque<T>>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let inner = VecDeque::<T>::deserialize(deserializer)?;
+        match inner.is_empty() {
+            true => Err(serde::de::Error::invalid_length(0, &"at least one item")),
+            false => Ok(Self(inner)),
+        }
+    }
+}
+
 impl<T> NonEmpty<T> {
     pub fn vecdeque(value: T) -> NonEmpty<VecDeque<T>> {
         NonEmpty::<VecDeque<T>>::new(value)
     }
 }
 
+impl<T> IntoIterator for NonEmpty<VecDeque<T>> {
+    type Item = T;
+    type IntoIter = std::collections::vec_deque::IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
 impl<T> NonEmpty<VecDeque<T>> {
     pub fn new(value: T) -> Self {
         Self(VecDeque::new().tap_mut(|it| it.push_back(value)))
@@ -27,12 +51,25 @@ impl<T> NonEmpty<VecDeque<T>> {
             _ => (Some(self), value),
         }
     }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
     pub fn front(&self) -> &T {
         self.0.front().expect("inner vecdeque is never empty")
     }
-    pub fn iter(&self) -> std::collections::vec_deque::Iter<T> {
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, T> {
         self.0.iter()
     }
+    pub fn iter_mut(&mut self) -> std::collections::vec_deque::IterMut<'_, T> {
+        self.0.iter_mut()
+    }
+    pub fn retain(mut self, condition: impl FnMut(&T) -> bool) -> Option<Self> {
+        self.0.retain(condition);
+        match self.0.len() {
+            0 => None,
+            _ => Some(self),
+        }
+    }
     /// # Panics
     /// - If no items match `condition`
     /// - If multiple items match `condition`
@@ -51,6 +88,8 @@ pub trait BTreeMapExt<KeyT, ValueT> {
     fn insert_uncontended(&mut self, key: KeyT, value: ValueT)
     where
         KeyT: Ord;
+    fn min(&self) -> Option<&KeyT>;
+    fn max(&self) -> Option<&KeyT>;
 }
 
 impl<KeyT, ValueT> BTreeMapExt<KeyT, ValueT> for BTreeMap<KeyT, ValueT> {
@@ -63,4 +102,31 @@ impl<KeyT, ValueT> BTreeMapExt<KeyT, ValueT> for BTreeMap<KeyT, ValueT> {
             panic!("key was contended")
         }
     }
+    fn min(&self) -> Option<&KeyT> {
+        self.keys().next()
+    }
+    fn max(&self) -> Option<&KeyT> {
+        self.keys().next_back()
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike [`std::hash::DefaultHasher`] is stable across releases.
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
 }
@@ -0,0 +1,84 @@
+//! A compact binary encoding, for [`PriceLevelBTreeOrderBook::encode`](crate::price_level_b_tree_order_book::PriceLevelBTreeOrderBook::encode).
+//!
+//! Integers are little-endian, and `usize` is always 8 bytes wide.
+
+use crate::scaled_price::ScaledPrice;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum DecodeError {
+    #[error("input ended early")]
+    Truncated,
+    #[error("input continues after the book")]
+    TrailingBytes,
+    #[error("a value is out of range for its type")]
+    OutOfRange,
+    #[error("price levels are not strictly in priority order")]
+    BadOrdering,
+    #[error("a price level has no orders")]
+    EmptyLevel,
+    #[error("an order has no quantity")]
+    ZeroQuantity,
+    #[error("an order is present more than once")]
+    DuplicateId,
+    #[error("the best bid is at or above the best ask")]
+    Crossed,
+}
+
+/// A type with a fixed binary encoding.
+pub trait Wire: Sized {
+    fn write(&self, bytes: &mut Vec<u8>);
+    /// Read a value from the front of `bytes`, advancing past it.
+    fn read(bytes: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], DecodeError> {
+    let (head, tail) = bytes.split_first_chunk().ok_or(DecodeError::Truncated)?;
+    *bytes = tail;
+    Ok(*head)
+}
+
+macro_rules! wire_int {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Wire for $ty {
+                fn write(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes())
+                }
+                fn read(bytes: &mut &[u8]) -> Result<Self, DecodeError> {
+                    take(bytes).map(Self::from_le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+wire_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Wire for usize {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        (*self as u64).write(bytes)
+    }
+    fn read(bytes: &mut &[u8]) -> Result<Self, DecodeError> {
+        u64::read(bytes)?
+            .try_into()
+            .map_err(|_| DecodeError::OutOfRange)
+    }
+}
+
+impl Wire for uuid::Uuid {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.as_bytes())
+    }
+    fn read(bytes: &mut &[u8]) -> Result<Self, DecodeError> {
+        take(bytes).map(Self::from_bytes)
+    }
+}
+
+impl<const DECIMALS: u32> Wire for ScaledPrice<DECIMALS> {
+    fn write(&self, bytes: &mut Vec<u8>) {
+        self.scaled().write(bytes)
+    }
+    fn read(bytes: &mut &[u8]) -> Result<Self, DecodeError> {
+        i64::read(bytes).map(Self::from_scaled)
+    }
+}